@@ -0,0 +1,74 @@
+use anyhow::Result;
+use std::{path::Path, process::Command};
+
+use crate::{cfg::Ini, write_waydroid_cfg, WAYDROID_CFG};
+
+/// (waydroid.cfg key, image, build.prop locations inside it, props tried in order)
+const IMAGES: [(&str, &str, &[&str], &[&str]); 2] = [
+    (
+        "system_datetime",
+        "system.img",
+        &["/system/build.prop", "/build.prop"],
+        &["ro.system.build.date.utc", "ro.build.date.utc"],
+    ),
+    (
+        "vendor_datetime",
+        "vendor.img",
+        &["/build.prop", "/etc/build.prop"],
+        &["ro.vendor.build.date.utc", "ro.build.date.utc"],
+    ),
+];
+
+/// Sets `system_datetime`/`vendor_datetime` to the build dates of the
+/// profile's images, so Waydroid does not treat a switched-in older build
+/// as something to upgrade over. Unreadable images leave the key as it is.
+pub fn sync(path: &Path, logs: &mut Vec<String>) -> Result<()> {
+    let mut ini = Ini::load(Path::new(WAYDROID_CFG))?;
+    let mut changed = false;
+    for (key, image, locations, props) in IMAGES {
+        let Some(date) = read_build_date(&path.join(image), locations, props) else {
+            logs.push(format!(
+                "build date warning: no ro.build.date.utc in {}, {} left as is",
+                image, key
+            ));
+            continue;
+        };
+        let date = date.to_string();
+        if ini.get("waydroid", key).as_deref() != Some(date.as_str()) {
+            ini.set("waydroid", key, &date);
+            changed = true;
+        }
+        logs.push(format!("build date: {} = {}", key, date));
+    }
+    if changed {
+        write_waydroid_cfg(&ini)?;
+    }
+    Ok(())
+}
+
+/// First of `props` found in a build.prop inside the ext4 `image`, read
+/// with `debugfs` so nothing has to be mounted.
+fn read_build_date(image: &Path, locations: &[&str], props: &[&str]) -> Option<u64> {
+    for location in locations {
+        let out = Command::new("debugfs")
+            .arg("-R")
+            .arg(format!("cat {}", location))
+            .arg(image)
+            .output()
+            .ok()?;
+        if !out.status.success() {
+            continue;
+        }
+        let text = String::from_utf8_lossy(&out.stdout);
+        for prop in props {
+            let value = text.lines().find_map(|line| {
+                let (key, value) = line.split_once('=')?;
+                (key.trim() == *prop).then(|| value.trim().parse().ok())?
+            });
+            if value.is_some() {
+                return value;
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,433 @@
+use anyhow::{bail, Context, Result};
+use dirs::home_dir;
+use serde::{Deserialize, Serialize};
+use std::{
+    env, fs,
+    os::unix::fs::symlink,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    history, lock, profile_id_from_path,
+    profile_ops::{find_profile, new_profile_path},
+    profiles_root, run_cmd,
+    snapshot::stop_if_running,
+    ImageProfile, APP_VERSION, PROFILE_META_FILE, STORE_DIRS, TAR_PRESERVE,
+};
+
+const MANIFEST_FILE: &str = "manifest.json";
+const BUNDLE_FORMAT: u32 = 1;
+const IMAGES: [&str; 2] = ["system.img", "vendor.img"];
+
+/// `manifest.json` at the top of a bundle. The rest of the archive holds
+/// `profile.cfg`, the store dirs and, if included, the images.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format: u32,
+    pub name: String,
+    pub created: u64,
+    pub tool_version: String,
+    pub source_host: String,
+    pub source_path: String,
+    pub images_included: bool,
+    pub images: Vec<ImageEntry>,
+    pub store_dirs: Vec<String>,
+    pub has_meta: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ImageEntry {
+    pub name: String,
+    pub bytes: u64,
+    pub sha256: String,
+}
+
+/// Writes `profile` to `out` as a tar.zst bundle. The images are always
+/// checksummed but only packed with `with_images`.
+pub fn export(
+    profile: &ImageProfile,
+    out: &Path,
+    with_images: bool,
+) -> Result<(PathBuf, Vec<String>)> {
+    let _lock = lock::acquire("export")?;
+    let home = home_dir().context("Failed to resolve HOME")?;
+    let store = profiles_root(&home).join(profile_id_from_path(&profile.path, &home));
+    if out.exists() {
+        bail!("{} already exists", out.display());
+    }
+
+    let mut logs = Vec::new();
+    let mut images = Vec::new();
+    for name in IMAGES {
+        let image = fs::canonicalize(profile.path.join(name))
+            .with_context(|| format!("Failed resolving {}", profile.path.join(name).display()))?;
+        let bytes = fs::metadata(&image)
+            .with_context(|| format!("Failed reading {}", image.display()))?
+            .len();
+        images.push(ImageEntry {
+            name: name.to_string(),
+            bytes,
+            sha256: sha256(&image)?,
+        });
+        logs.push(format!("export: checksummed {}", image.display()));
+    }
+    let store_dirs: Vec<String> = STORE_DIRS
+        .iter()
+        .filter(|name| store.join(name).exists())
+        .map(|name| name.to_string())
+        .collect();
+    let has_meta = profile.path.join(PROFILE_META_FILE).is_file();
+
+    let manifest = Manifest {
+        format: BUNDLE_FORMAT,
+        name: profile.name.clone(),
+        created: history::unix_now(),
+        tool_version: APP_VERSION.to_string(),
+        source_host: fs::read_to_string("/proc/sys/kernel/hostname")
+            .map(|h| h.trim().to_string())
+            .unwrap_or_default(),
+        source_path: profile.path.display().to_string(),
+        images_included: with_images,
+        images,
+        store_dirs,
+        has_meta,
+    };
+
+    stop_if_running(profile, &mut logs);
+    let staging = staging_dir("export")?;
+    let result = write_bundle(profile, &store, &manifest, &staging, out, &mut logs);
+    let _ = fs::remove_dir_all(&staging);
+    if let Err(err) = result {
+        let _ = run_cmd("sudo", &["rm", "-f", &out.to_string_lossy()]);
+        return Err(err);
+    }
+    logs.push(format!("export: wrote {}", out.display()));
+    Ok((out.to_path_buf(), logs))
+}
+
+fn write_bundle(
+    profile: &ImageProfile,
+    store: &Path,
+    manifest: &Manifest,
+    staging: &Path,
+    out: &Path,
+    logs: &mut Vec<String>,
+) -> Result<()> {
+    fs::write(
+        staging.join(MANIFEST_FILE),
+        serde_json::to_string_pretty(manifest)?,
+    )
+    .context("Failed writing manifest")?;
+    if manifest.has_meta {
+        fs::copy(
+            profile.path.join(PROFILE_META_FILE),
+            staging.join(PROFILE_META_FILE),
+        )
+        .context("Failed copying profile.cfg")?;
+    }
+
+    // Plain tar first so the images can be appended dereferenced without
+    // following the symlinks inside the userdata.
+    let tar = out.with_extension("partial.tar");
+    let tar_s = tar.to_string_lossy().to_string();
+    let staging_s = staging.to_string_lossy().to_string();
+    let store_s = store.to_string_lossy().to_string();
+    let mut args = vec!["tar"];
+    args.extend(TAR_PRESERVE);
+    args.extend(["-cpf", &tar_s, "-C", &staging_s, "--", MANIFEST_FILE]);
+    if manifest.has_meta {
+        args.push(PROFILE_META_FILE);
+    }
+    run_cmd("sudo", &args)?;
+    if !manifest.store_dirs.is_empty() {
+        let mut args = vec!["tar"];
+        args.extend(TAR_PRESERVE);
+        args.extend(["-rpf", &tar_s, "-C", &store_s, "--"]);
+        args.extend(manifest.store_dirs.iter().map(String::as_str));
+        run_cmd("sudo", &args)?;
+    }
+    logs.push(format!(
+        "export: packed userdata/overlays ({})",
+        if manifest.store_dirs.is_empty() {
+            "none".to_string()
+        } else {
+            manifest.store_dirs.join(", ")
+        }
+    ));
+
+    if manifest.images_included {
+        let dir_s = profile.path.to_string_lossy().to_string();
+        let mut args = vec!["tar", "-rhf", tar_s.as_str(), "-C", dir_s.as_str(), "--"];
+        args.extend(IMAGES);
+        run_cmd("sudo", &args)?;
+        logs.push("export: packed system.img and vendor.img".to_string());
+    }
+
+    let out_s = out.to_string_lossy().to_string();
+    let compressed = run_cmd("sudo", &["zstd", "-q", "-T0", "--rm", &tar_s, "-o", &out_s]);
+    if compressed.is_err() {
+        let _ = run_cmd("sudo", &["rm", "-f", &tar_s]);
+    }
+    compressed?;
+    // Root-owned after the sudo steps; hand it back to the invoking user.
+    if let Ok(user) = env::var("SUDO_USER").or_else(|_| env::var("USER")) {
+        let _ = run_cmd("sudo", &["chown", &user, &out_s]);
+    }
+    Ok(())
+}
+
+/// Recreates a bundle as `~/waydroid-images/<name>` plus its profile store.
+/// Bundles without images need `images_dir` holding matching
+/// `system.img`/`vendor.img`, which are symlinked like a manual add.
+pub fn import(
+    bundle: &Path,
+    name: Option<&str>,
+    images_dir: Option<&Path>,
+) -> Result<(PathBuf, Vec<String>)> {
+    let _lock = lock::acquire("import")?;
+    let home = home_dir().context("Failed to resolve HOME")?;
+    let manifest = read_manifest(bundle)?;
+    if manifest.format != BUNDLE_FORMAT {
+        bail!(
+            "{} uses bundle format {}, this version reads {}",
+            bundle.display(),
+            manifest.format,
+            BUNDLE_FORMAT
+        );
+    }
+    check_members(&manifest)?;
+    if !manifest.images_included && images_dir.is_none() {
+        bail!("The bundle has no images; pass --images <DIR> with system.img and vendor.img");
+    }
+
+    let new_path = new_profile_path(&home, name.unwrap_or(&manifest.name))?;
+    let store = profiles_root(&home).join(profile_id_from_path(&new_path, &home));
+    if store.exists() {
+        bail!("Profile store {} already exists", store.display());
+    }
+
+    let mut logs = vec![format!(
+        "import: '{}' exported {} from {}:{}",
+        manifest.name,
+        history::format_timestamp(manifest.created),
+        manifest.source_host,
+        manifest.source_path
+    )];
+    let result = unpack_bundle(bundle, &manifest, &new_path, &store, images_dir, &mut logs);
+    if let Err(err) = result {
+        let _ = fs::remove_dir_all(&new_path);
+        let _ = run_cmd("sudo", &["rm", "-rf", &store.to_string_lossy()]);
+        return Err(err);
+    }
+    logs.push(format!("import: created {}", new_path.display()));
+    Ok((new_path, logs))
+}
+
+fn unpack_bundle(
+    bundle: &Path,
+    manifest: &Manifest,
+    new_path: &Path,
+    store: &Path,
+    images_dir: Option<&Path>,
+    logs: &mut Vec<String>,
+) -> Result<()> {
+    let bundle_s = bundle.to_string_lossy().to_string();
+    fs::create_dir_all(new_path)
+        .with_context(|| format!("Failed creating {}", new_path.display()))?;
+    let dir_s = new_path.to_string_lossy().to_string();
+
+    let mut members: Vec<&str> = Vec::new();
+    if manifest.has_meta {
+        members.push(PROFILE_META_FILE);
+    }
+    if manifest.images_included {
+        members.extend(IMAGES);
+    }
+    if !members.is_empty() {
+        let mut args = vec![
+            "--zstd",
+            "-xf",
+            bundle_s.as_str(),
+            "-C",
+            dir_s.as_str(),
+            "--",
+        ];
+        args.extend(members.iter().copied());
+        run_cmd("tar", &args)?;
+    }
+
+    if let (false, Some(images_dir)) = (manifest.images_included, images_dir) {
+        for name in IMAGES {
+            let source = fs::canonicalize(images_dir.join(name))
+                .with_context(|| format!("Failed resolving {}", images_dir.join(name).display()))?;
+            symlink(&source, new_path.join(name))
+                .with_context(|| format!("Failed linking {}", source.display()))?;
+        }
+        logs.push(format!(
+            "import: linked images from {}",
+            images_dir.display()
+        ));
+    }
+
+    for entry in &manifest.images {
+        let actual = sha256(&new_path.join(&entry.name))?;
+        if actual != entry.sha256 {
+            bail!(
+                "{} does not match the exported image (sha256 {} != {})",
+                entry.name,
+                actual,
+                entry.sha256
+            );
+        }
+    }
+    logs.push("import: image checksums match".to_string());
+
+    fs::create_dir_all(store).with_context(|| format!("Failed creating {}", store.display()))?;
+    if !manifest.store_dirs.is_empty() {
+        let store_s = store.to_string_lossy().to_string();
+        let mut args = vec!["tar", "--zstd"];
+        args.extend(TAR_PRESERVE);
+        args.extend(["-xpf", bundle_s.as_str(), "-C", store_s.as_str(), "--"]);
+        args.extend(manifest.store_dirs.iter().map(String::as_str));
+        run_cmd("sudo", &args)?;
+        logs.push(format!(
+            "import: restored {} into {}",
+            manifest.store_dirs.join(", "),
+            store.display()
+        ));
+    }
+    Ok(())
+}
+
+/// The manifest's names end up in tar argv run as root and in paths under
+/// the new profile, so only the names this tool writes are accepted.
+fn check_members(manifest: &Manifest) -> Result<()> {
+    if let Some(dir) = manifest
+        .store_dirs
+        .iter()
+        .find(|dir| !STORE_DIRS.contains(&dir.as_str()))
+    {
+        bail!("The bundle manifest lists an unknown store dir '{}'", dir);
+    }
+    if let Some(entry) = manifest
+        .images
+        .iter()
+        .find(|entry| !IMAGES.contains(&entry.name.as_str()))
+    {
+        bail!(
+            "The bundle manifest lists an unknown image '{}'",
+            entry.name
+        );
+    }
+    Ok(())
+}
+
+/// `waydroid-switch export [--with-images] <NAME> [FILE]`
+pub fn run_export(args: &[String]) -> Result<()> {
+    let with_images = args.iter().any(|a| a == "--with-images");
+    let rest: Vec<&String> = args.iter().filter(|a| !a.starts_with("--")).collect();
+    let (name, out) = match rest.as_slice() {
+        [name] => (*name, None),
+        [name, out] => (*name, Some(PathBuf::from(out))),
+        _ => bail!("export expects a profile name and optionally an output file"),
+    };
+    let profile = find_profile(name)?;
+    let out = out.unwrap_or_else(|| {
+        PathBuf::from(format!(
+            "{}.wsbundle.tar.zst",
+            profile.name.replace('/', "-")
+        ))
+    });
+    let (_, logs) = export(&profile, &out, with_images)?;
+    println!("{}", logs.join("\n"));
+    Ok(())
+}
+
+/// `waydroid-switch import [--images DIR] <FILE> [NAME]`
+pub fn run_import(args: &[String]) -> Result<()> {
+    let mut images_dir = None;
+    let mut rest = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--images" {
+            images_dir = Some(PathBuf::from(
+                iter.next().context("--images expects a directory")?,
+            ));
+        } else {
+            rest.push(arg.as_str());
+        }
+    }
+    let (file, name) = match rest.as_slice() {
+        [file] => (*file, None),
+        [file, name] => (*file, Some(*name)),
+        _ => bail!("import expects a bundle file and optionally a profile name"),
+    };
+    let (_, logs) = import(Path::new(file), name, images_dir.as_deref())?;
+    println!("{}", logs.join("\n"));
+    Ok(())
+}
+
+fn read_manifest(bundle: &Path) -> Result<Manifest> {
+    let bundle_s = bundle.to_string_lossy().to_string();
+    let raw = run_cmd("tar", &["--zstd", "-xOf", &bundle_s, "--", MANIFEST_FILE])
+        .with_context(|| format!("{} is not a profile bundle", bundle.display()))?;
+    serde_json::from_str(&raw).with_context(|| format!("Invalid manifest in {}", bundle.display()))
+}
+
+fn sha256(path: &Path) -> Result<String> {
+    let path_s = path.to_string_lossy().to_string();
+    let out = run_cmd("sha256sum", &[&path_s])?;
+    out.split_whitespace()
+        .next()
+        .map(str::to_string)
+        .with_context(|| format!("No checksum for {}", path.display()))
+}
+
+fn staging_dir(operation: &str) -> Result<PathBuf> {
+    let dir = env::temp_dir().join(format!(
+        "waydroid-switch-{}-{}",
+        operation,
+        std::process::id()
+    ));
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+    fs::create_dir_all(&dir).with_context(|| format!("Failed creating {}", dir.display()))?;
+    Ok(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(store_dirs: &[&str], image: &str) -> Manifest {
+        Manifest {
+            format: BUNDLE_FORMAT,
+            name: "a".to_string(),
+            created: 0,
+            tool_version: APP_VERSION.to_string(),
+            source_host: String::new(),
+            source_path: String::new(),
+            images_included: false,
+            images: vec![ImageEntry {
+                name: image.to_string(),
+                bytes: 0,
+                sha256: String::new(),
+            }],
+            store_dirs: store_dirs.iter().map(|d| d.to_string()).collect(),
+            has_meta: false,
+        }
+    }
+
+    #[test]
+    fn check_members_accepts_known_names() {
+        assert!(check_members(&manifest(&STORE_DIRS, "system.img")).is_ok());
+    }
+
+    #[test]
+    fn check_members_rejects_unknown_names() {
+        assert!(check_members(&manifest(&["--checkpoint-action=exec=id"], "system.img")).is_err());
+        assert!(check_members(&manifest(&["data"], "../../system.img")).is_err());
+        assert!(check_members(&manifest(&["data"], "/etc/passwd")).is_err());
+    }
+}
@@ -0,0 +1,219 @@
+use anyhow::{Context, Result};
+use std::{fs, path::Path};
+
+/// Minimal line-preserving reader and editor for the `key = value` INI files Waydroid
+/// writes with Python's configparser.
+#[derive(Clone, Debug, Default)]
+pub struct Ini {
+    lines: Vec<String>,
+}
+
+impl Ini {
+    pub fn parse(text: &str) -> Self {
+        Self {
+            lines: text.lines().map(str::to_string).collect(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Ok(Self::parse(&text))
+    }
+
+    /// All `(key, value)` pairs of `section` in file order.
+    pub fn section(&self, section: &str) -> Vec<(String, String)> {
+        let mut current: Option<&str> = None;
+        let mut out = Vec::new();
+        for line in &self.lines {
+            let trimmed = line.trim();
+            if let Some(name) = section_name(trimmed) {
+                current = Some(name);
+                continue;
+            }
+            if current != Some(section) {
+                continue;
+            }
+            if let Some((key, value)) = parse_entry(trimmed) {
+                out.push((key.to_string(), value.to_string()));
+            }
+        }
+        out
+    }
+
+    pub fn get(&self, section: &str, key: &str) -> Option<String> {
+        self.section(section)
+            .into_iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v)
+    }
+
+    /// Rewrites the first `key` line of `section`, or appends it at the end
+    /// of the section (creating the section if needed).
+    pub fn set(&mut self, section: &str, key: &str, value: &str) {
+        let line = format!("{} = {}", key, value);
+        if let Some(idx) = self.find_entry(section, key) {
+            self.lines[idx] = line;
+            return;
+        }
+        match self.section_end(section) {
+            Some(end) => self.lines.insert(end, line),
+            None => {
+                if self.lines.last().is_some_and(|l| !l.trim().is_empty()) {
+                    self.lines.push(String::new());
+                }
+                self.lines.push(format!("[{}]", section));
+                self.lines.push(line);
+            }
+        }
+    }
+
+    /// Drops every `key` line of `section`; returns whether one existed.
+    pub fn remove(&mut self, section: &str, key: &str) -> bool {
+        let mut removed = false;
+        while let Some(idx) = self.find_entry(section, key) {
+            self.lines.remove(idx);
+            removed = true;
+        }
+        removed
+    }
+
+    pub fn render(&self) -> String {
+        let mut text = self.lines.join("\n");
+        text.push('\n');
+        text
+    }
+
+    fn find_entry(&self, section: &str, key: &str) -> Option<usize> {
+        let mut current: Option<&str> = None;
+        for (idx, line) in self.lines.iter().enumerate() {
+            let trimmed = line.trim();
+            if let Some(name) = section_name(trimmed) {
+                current = Some(name);
+            } else if current == Some(section)
+                && parse_entry(trimmed).is_some_and(|(k, _)| k == key)
+            {
+                return Some(idx);
+            }
+        }
+        None
+    }
+
+    /// Index just after the last entry of `section`, `None` if it is absent.
+    fn section_end(&self, section: &str) -> Option<usize> {
+        let mut current: Option<&str> = None;
+        let mut end = None;
+        for (idx, line) in self.lines.iter().enumerate() {
+            let trimmed = line.trim();
+            if let Some(name) = section_name(trimmed) {
+                current = Some(name);
+                if name == section {
+                    end = Some(idx + 1);
+                }
+            } else if current == Some(section) && !trimmed.is_empty() {
+                end = Some(idx + 1);
+            }
+        }
+        end
+    }
+}
+
+fn section_name(line: &str) -> Option<&str> {
+    line.strip_prefix('[')?.strip_suffix(']').map(str::trim)
+}
+
+fn parse_entry(line: &str) -> Option<(&str, &str)> {
+    if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+        return None;
+    }
+    let (key, value) = line.split_once('=')?;
+    Some((key.trim(), value.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CFG: &str = "\
+[waydroid]
+arch = x86_64
+images_path = /usr/share/waydroid-extra/images
+# mount_overlays = False
+
+[properties]
+ro.hardware.gralloc = default
+";
+
+    #[test]
+    fn section_reads_entries_in_order_and_skips_comments() {
+        let ini = Ini::parse(CFG);
+        assert_eq!(
+            ini.section("waydroid"),
+            vec![
+                ("arch".to_string(), "x86_64".to_string()),
+                (
+                    "images_path".to_string(),
+                    "/usr/share/waydroid-extra/images".to_string()
+                ),
+            ]
+        );
+        assert!(ini.section("missing").is_empty());
+        assert_eq!(ini.get("waydroid", "mount_overlays"), None);
+    }
+
+    #[test]
+    fn set_rewrites_an_existing_key_in_place() {
+        let mut ini = Ini::parse(CFG);
+        ini.set("waydroid", "arch", "arm64");
+        assert_eq!(ini.render(), CFG.replace("x86_64", "arm64"));
+    }
+
+    #[test]
+    fn set_appends_after_the_last_entry_of_the_section() {
+        let mut ini = Ini::parse(CFG);
+        ini.set("waydroid", "suspend_action", "stop");
+        let text = ini.render();
+        assert!(text.contains("# mount_overlays = False\nsuspend_action = stop\n\n[properties]"));
+        assert_eq!(
+            ini.get("waydroid", "suspend_action").as_deref(),
+            Some("stop")
+        );
+        assert_eq!(ini.get("properties", "suspend_action"), None);
+    }
+
+    #[test]
+    fn set_creates_a_missing_section_at_the_end() {
+        let mut ini = Ini::parse("[waydroid]\narch = x86_64");
+        ini.set("properties", "ro.foo", "1");
+        assert_eq!(
+            ini.render(),
+            "[waydroid]\narch = x86_64\n\n[properties]\nro.foo = 1\n"
+        );
+    }
+
+    #[test]
+    fn set_fills_an_empty_section() {
+        let mut ini = Ini::parse("[properties]\n\n[waydroid]\narch = x86_64\n");
+        ini.set("properties", "ro.foo", "1");
+        assert_eq!(
+            ini.render(),
+            "[properties]\nro.foo = 1\n\n[waydroid]\narch = x86_64\n"
+        );
+    }
+
+    #[test]
+    fn remove_drops_only_the_key_in_that_section() {
+        let mut ini = Ini::parse("[a]\nk = 1\nk = 2\nother = 3\n[b]\nk = 4\n");
+        assert!(ini.remove("a", "k"));
+        assert!(!ini.remove("a", "k"));
+        assert_eq!(ini.render(), "[a]\nother = 3\n[b]\nk = 4\n");
+    }
+
+    #[test]
+    fn section_end_is_none_for_a_missing_section() {
+        let ini = Ini::parse(CFG);
+        assert_eq!(ini.section_end("nope"), None);
+        assert_eq!(ini.section_end("waydroid"), Some(4));
+        assert_eq!(ini.section_end("properties"), Some(7));
+    }
+}
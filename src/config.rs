@@ -0,0 +1,114 @@
+use anyhow::{Context, Result};
+use dirs::home_dir;
+use std::path::PathBuf;
+
+use crate::cfg::Ini;
+
+/// User settings from `~/.config/waydroid-switch/config.cfg`:
+///
+/// ```ini
+/// [switch]
+/// confirm = true
+/// upgrade = never
+///
+/// [snapshot]
+/// before_switch = false
+/// keep = 5
+/// ```
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub confirm_switch: bool,
+    /// When a switch runs `waydroid upgrade --offline`; a change to the
+    /// `[properties]` overrides always runs it.
+    pub upgrade: UpgradeMode,
+    /// Snapshot the outgoing profile's store before switching away.
+    pub snapshot_before_switch: bool,
+    /// Pre-switch snapshots kept per profile, 0 keeps all.
+    pub snapshot_keep: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            confirm_switch: true,
+            upgrade: UpgradeMode::Never,
+            snapshot_before_switch: false,
+            snapshot_keep: 5,
+        }
+    }
+}
+
+impl Config {
+    /// Missing file or keys fall back to the defaults.
+    pub fn load() -> Self {
+        let Ok(path) = config_path() else {
+            return Self::default();
+        };
+        let Ok(ini) = Ini::load(&path) else {
+            return Self::default();
+        };
+
+        let mut config = Self::default();
+        for (key, value) in ini.section("switch") {
+            match key.as_str() {
+                "confirm" => {
+                    config.confirm_switch = parse_bool(&value).unwrap_or(config.confirm_switch);
+                }
+                "upgrade" => {
+                    config.upgrade = UpgradeMode::parse(&value).unwrap_or(config.upgrade);
+                }
+                _ => {}
+            }
+        }
+        for (key, value) in ini.section("snapshot") {
+            match key.as_str() {
+                "before_switch" => {
+                    config.snapshot_before_switch =
+                        parse_bool(&value).unwrap_or(config.snapshot_before_switch);
+                }
+                "keep" => {
+                    config.snapshot_keep = value.parse().unwrap_or(config.snapshot_keep);
+                }
+                _ => {}
+            }
+        }
+        config
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UpgradeMode {
+    Never,
+    /// Only when the vendor image changed.
+    Auto,
+    Always,
+}
+
+impl UpgradeMode {
+    fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "never" => Some(Self::Never),
+            "auto" => Some(Self::Auto),
+            "always" => Some(Self::Always),
+            _ => None,
+        }
+    }
+}
+
+pub fn config_path() -> Result<PathBuf> {
+    let base = match dirs::config_dir() {
+        Some(dir) => dir,
+        None => home_dir()
+            .context("Failed to resolve HOME")?
+            .join(".config"),
+    };
+    Ok(base.join("waydroid-switch").join("config.cfg"))
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Some(true),
+        "false" | "no" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
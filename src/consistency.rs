@@ -0,0 +1,132 @@
+use anyhow::{Context, Result};
+use dirs::home_dir;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    current_images_path, profile_id_from_path, profiles_root, ImageProfile, OVERLAY_RW_LIVE,
+    OVERLAY_WORK_LIVE,
+};
+
+/// One piece of live Waydroid state and the profile it currently belongs to.
+#[derive(Clone, Debug)]
+pub struct StateEntry {
+    pub label: &'static str,
+    pub target: Option<PathBuf>,
+    pub profile_id: Option<String>,
+}
+
+impl StateEntry {
+    fn describe(&self) -> String {
+        match (&self.profile_id, &self.target) {
+            (Some(id), _) => format!("{} -> {}", self.label, id),
+            (None, Some(target)) => format!("{} -> {} (no profile)", self.label, target.display()),
+            (None, None) => format!("{} -> not linked", self.label),
+        }
+    }
+}
+
+/// Snapshot of `images_path` plus the userdata and overlay links, used to
+/// spot a half-finished switch or a manual `waydroid init`.
+#[derive(Clone, Debug)]
+pub struct Consistency {
+    pub entries: Vec<StateEntry>,
+}
+
+impl Consistency {
+    pub fn check() -> Result<Self> {
+        let home = home_dir().context("Failed to resolve HOME")?;
+        let root = profiles_root(&home);
+
+        let images = current_images_path().ok().map(PathBuf::from);
+        let images_id = images.as_ref().map(|p| profile_id_from_path(p, &home));
+
+        let entries = vec![
+            StateEntry {
+                label: "images_path",
+                target: images,
+                profile_id: images_id,
+            },
+            link_entry("userdata", &home.join(".local/share/waydroid/data"), &root),
+            link_entry("overlay_rw", Path::new(OVERLAY_RW_LIVE), &root),
+            link_entry("overlay_work", Path::new(OVERLAY_WORK_LIVE), &root),
+        ];
+
+        Ok(Self { entries })
+    }
+
+    /// The switcher is considered unused until at least one live link points
+    /// into the profile store; after that every entry must agree.
+    pub fn is_consistent(&self) -> bool {
+        let links_in_store = self.entries[1..].iter().any(|e| e.profile_id.is_some());
+        if !links_in_store {
+            return true;
+        }
+        let ids = self.profile_ids();
+        ids.len() == 1 && self.entries.iter().all(|e| e.profile_id.is_some())
+    }
+
+    pub fn summary(&self) -> String {
+        self.entries
+            .iter()
+            .map(StateEntry::describe)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Distinct profile ids referenced by the live state, in entry order.
+    pub fn profile_ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = Vec::new();
+        for id in self.entries.iter().filter_map(|e| e.profile_id.clone()) {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+        ids
+    }
+
+    /// Known profiles the state can be reconciled to, with the entries that
+    /// already reference each of them.
+    pub fn candidates(&self, profiles: &[ImageProfile]) -> Vec<(usize, Vec<&'static str>)> {
+        let Some(home) = home_dir() else {
+            return Vec::new();
+        };
+        self.profile_ids()
+            .into_iter()
+            .filter_map(|id| {
+                let idx = profiles
+                    .iter()
+                    .position(|p| profile_id_from_path(&p.path, &home) == id)?;
+                let labels = self
+                    .entries
+                    .iter()
+                    .filter(|e| e.profile_id.as_deref() == Some(id.as_str()))
+                    .map(|e| e.label)
+                    .collect();
+                Some((idx, labels))
+            })
+            .collect()
+    }
+}
+
+fn link_entry(label: &'static str, live: &Path, root: &Path) -> StateEntry {
+    let target = fs::read_link(live).ok();
+    let profile_id = target
+        .as_deref()
+        .and_then(|t| profile_id_from_store_path(t, root));
+    StateEntry {
+        label,
+        target,
+        profile_id,
+    }
+}
+
+/// Maps `<profiles root>/<id>/...` back to `<id>`.
+pub fn profile_id_from_store_path(path: &Path, root: &Path) -> Option<String> {
+    let rel = path.strip_prefix(root).ok()?;
+    rel.components()
+        .next()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+}
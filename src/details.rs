@@ -0,0 +1,310 @@
+use anyhow::{Context, Result};
+use dirs::home_dir;
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::UNIX_EPOCH,
+};
+
+use crate::{
+    disk_usage_kb, history, human_size_kb, profile_id_from_path, profiles_root, ImageProfile,
+    STORE_DIRS,
+};
+
+#[derive(Clone, Debug)]
+pub struct ImageInfo {
+    pub name: &'static str,
+    pub link_target: Option<PathBuf>,
+    pub size_bytes: Option<u64>,
+    pub modified: Option<u64>,
+    pub fs_type: &'static str,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DirSize {
+    Missing,
+    /// `du` is still running on the [`SizeCache`] worker.
+    Measuring,
+    Known(u64),
+    Unknown,
+}
+
+/// Everything the details pane shows for one profile. Collected on every
+/// selection change, so it only stats; store dir sizes come from a
+/// [`SizeCache`].
+#[derive(Clone, Debug)]
+pub struct ProfileDetails {
+    pub path: PathBuf,
+    pub profile_id: String,
+    pub images: Vec<ImageInfo>,
+    pub store_dirs: Vec<(&'static str, PathBuf, DirSize)>,
+    pub last_used: Option<u64>,
+}
+
+impl ProfileDetails {
+    pub fn collect(profile: &ImageProfile, last_used: Option<u64>) -> Result<Self> {
+        let home = home_dir().context("Failed to resolve HOME")?;
+        let profile_id = profile_id_from_path(&profile.path, &home);
+        let store = profiles_root(&home).join(&profile_id);
+
+        let images = ["system.img", "vendor.img"]
+            .into_iter()
+            .map(|name| image_info(name, &profile.path.join(name)))
+            .collect();
+
+        let store_dirs = STORE_DIRS
+            .into_iter()
+            .map(|name| (name, store.join(name), DirSize::Measuring))
+            .collect();
+
+        Ok(Self {
+            path: profile.path.clone(),
+            profile_id,
+            images,
+            store_dirs,
+            last_used,
+        })
+    }
+
+    pub fn lines(&self, profile: &ImageProfile) -> Vec<String> {
+        let mut lines = vec![
+            format!("Profile id: {}", self.profile_id),
+            format!("Folder: {}", self.path.display()),
+            format!(
+                "Last used: {}",
+                self.last_used
+                    .map(history::format_timestamp)
+                    .unwrap_or_else(|| "never (no recorded switch)".to_string())
+            ),
+            String::new(),
+        ];
+
+        for image in &self.images {
+            lines.push(format!("{} ({})", image.name, image.fs_type));
+            if let Some(target) = &image.link_target {
+                lines.push(format!("  -> {}", target.display()));
+            }
+            lines.push(format!(
+                "  {}, modified {}",
+                image
+                    .size_bytes
+                    .map(|b| human_size_kb(b / 1024))
+                    .unwrap_or_else(|| "missing".to_string()),
+                image
+                    .modified
+                    .map(history::format_timestamp)
+                    .unwrap_or_else(|| "?".to_string())
+            ));
+        }
+        lines.push(String::new());
+
+        for (name, dir, size) in &self.store_dirs {
+            let usage = match size {
+                DirSize::Known(kb) => human_size_kb(*kb),
+                DirSize::Measuring => "measuring...".to_string(),
+                DirSize::Unknown => "size unknown".to_string(),
+                DirSize::Missing => "not created yet".to_string(),
+            };
+            lines.push(format!("{}: {}", name, usage));
+            lines.push(format!("  {}", dir.display()));
+        }
+
+        if !profile.meta.tags.is_empty() || !profile.meta.fields.is_empty() {
+            lines.push(String::new());
+            if !profile.meta.tags.is_empty() {
+                lines.push(format!("tags: {}", profile.meta.tags.join(", ")));
+            }
+            for (key, value) in &profile.meta.fields {
+                lines.push(format!("{}: {}", key, value));
+            }
+        }
+        if !profile.meta.cfg_overrides.is_empty() {
+            lines.push(String::new());
+            lines.push("waydroid.cfg overrides:".to_string());
+            for (section, key, value) in &profile.meta.cfg_overrides {
+                lines.push(format!("  [{}] {} = {}", section, key, value));
+            }
+        }
+        lines
+    }
+
+    /// Takes the store dir sizes from `cache`, queueing the ones it lacks.
+    pub fn fill_sizes(&mut self, cache: &mut SizeCache) {
+        for (_, dir, size) in &mut self.store_dirs {
+            *size = if !dir.exists() {
+                DirSize::Missing
+            } else {
+                match cache.get(dir) {
+                    Some(Some(kb)) => DirSize::Known(kb),
+                    Some(None) => DirSize::Unknown,
+                    None => DirSize::Measuring,
+                }
+            };
+        }
+    }
+}
+
+/// Last successful switch to each profile path, from the history file.
+pub fn last_used_times() -> HashMap<String, u64> {
+    history::load()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|r| r.succeeded())
+        .map(|r| (r.to, r.timestamp))
+        .collect()
+}
+
+/// `du` results by directory, measured on a worker thread so moving through
+/// the list never waits for a large userdata dir.
+#[derive(Debug)]
+pub struct SizeCache {
+    sizes: HashMap<PathBuf, Option<u64>>,
+    queued: Vec<PathBuf>,
+    /// Bumped by `clear`; results of requests from before it are dropped.
+    generation: u64,
+    requests: Sender<(u64, PathBuf)>,
+    results: Receiver<(u64, PathBuf, Option<u64>)>,
+}
+
+impl SizeCache {
+    pub fn spawn() -> Self {
+        let (requests, jobs) = mpsc::channel::<(u64, PathBuf)>();
+        let (done, results) = mpsc::channel();
+        thread::spawn(move || {
+            for (generation, dir) in jobs {
+                let kb = disk_usage_kb(&dir);
+                if done.send((generation, dir, kb)).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            sizes: HashMap::new(),
+            queued: Vec::new(),
+            generation: 0,
+            requests,
+            results,
+        }
+    }
+
+    /// The measured size, or `None` (after queueing `dir`) if not known yet.
+    pub fn get(&mut self, dir: &Path) -> Option<Option<u64>> {
+        if let Some(kb) = self.sizes.get(dir) {
+            return Some(*kb);
+        }
+        if !self.queued.iter().any(|q| q == dir)
+            && self
+                .requests
+                .send((self.generation, dir.to_path_buf()))
+                .is_ok()
+        {
+            self.queued.push(dir.to_path_buf());
+        }
+        None
+    }
+
+    /// Picks up finished measurements; true if any arrived.
+    pub fn poll(&mut self) -> bool {
+        let mut any = false;
+        while let Ok((generation, dir, kb)) = self.results.try_recv() {
+            if generation != self.generation {
+                continue;
+            }
+            self.queued.retain(|q| *q != dir);
+            self.sizes.insert(dir, kb);
+            any = true;
+        }
+        any
+    }
+
+    /// Drops every cached size, e.g. after an operation changed a store.
+    pub fn clear(&mut self) {
+        self.sizes.clear();
+        self.queued.clear();
+        self.generation += 1;
+    }
+}
+
+fn image_info(name: &'static str, path: &Path) -> ImageInfo {
+    let link_target = fs::read_link(path)
+        .ok()
+        .map(|t| fs::canonicalize(path).unwrap_or(t));
+    let meta = fs::metadata(path).ok();
+    ImageInfo {
+        name,
+        link_target,
+        size_bytes: meta.as_ref().map(|m| m.len()),
+        modified: meta
+            .and_then(|m| m.modified().ok())
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs()),
+        fs_type: detect_fs_type(path),
+    }
+}
+
+/// Identifies the image format from its magic bytes.
+pub fn detect_fs_type(path: &Path) -> &'static str {
+    let Ok(mut file) = File::open(path) else {
+        return "unreadable";
+    };
+
+    let mut head = [0u8; 4];
+    if read_at(&mut file, 0, &mut head) {
+        if head == *b"hsqs" {
+            return "squashfs";
+        }
+        if u32::from_le_bytes(head) == 0xED26_FF3A {
+            return "android sparse";
+        }
+    }
+
+    let mut erofs = [0u8; 4];
+    if read_at(&mut file, 1024, &mut erofs) && u32::from_le_bytes(erofs) == 0xE0F5_E1E2 {
+        return "erofs";
+    }
+
+    let mut ext = [0u8; 2];
+    if read_at(&mut file, 1024 + 56, &mut ext) && u16::from_le_bytes(ext) == 0xEF53 {
+        return "ext4";
+    }
+
+    "unknown"
+}
+
+fn read_at(file: &mut File, offset: u64, buf: &mut [u8]) -> bool {
+    file.seek(SeekFrom::Start(offset)).is_ok() && file.read_exact(buf).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    fn poll_until(cache: &mut SizeCache, dir: &Path) -> Option<Option<u64>> {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            cache.poll();
+            if let Some(kb) = cache.get(dir) {
+                return Some(kb);
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        None
+    }
+
+    #[test]
+    fn size_cache_drops_results_from_before_clear() {
+        let dir = std::env::temp_dir();
+        let mut cache = SizeCache::spawn();
+        assert_eq!(cache.get(&dir), None);
+        cache.clear();
+        thread::sleep(Duration::from_millis(200));
+        assert!(!cache.poll());
+        assert_eq!(cache.get(&dir), None);
+        assert!(poll_until(&mut cache, &dir).is_some());
+    }
+}
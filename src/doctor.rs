@@ -0,0 +1,312 @@
+use anyhow::{Context, Result};
+use dirs::home_dir;
+use serde::Serialize;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{profiles_root, run_cmd, OVERLAY_RW_LIVE, OVERLAY_WORK_LIVE, WAYDROID_CFG};
+
+const LOW_SPACE_WARN_KB: u64 = 4 * 1024 * 1024;
+const LOW_SPACE_FAIL_KB: u64 = 512 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl Level {
+    fn tag(self) -> &'static str {
+        match self {
+            Level::Pass => "PASS",
+            Level::Warn => "WARN",
+            Level::Fail => "FAIL",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub level: Level,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: &'static str, level: Level, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            level,
+            detail: detail.into(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    checks: &'a [Check],
+    pass: usize,
+    warn: usize,
+    fail: usize,
+}
+
+/// `waydroid-switch doctor [--json]`. Exits non-zero when any check fails.
+pub fn run(args: &[String]) -> Result<()> {
+    let json = args.iter().any(|a| a == "--json");
+    let checks = run_checks()?;
+    let count = |level| checks.iter().filter(|c| c.level == level).count();
+    let report = Report {
+        checks: &checks,
+        pass: count(Level::Pass),
+        warn: count(Level::Warn),
+        fail: count(Level::Fail),
+    };
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        for check in &checks {
+            println!("[{}] {}: {}", check.level.tag(), check.name, check.detail);
+        }
+        println!(
+            "\n{} passed, {} warnings, {} failed",
+            report.pass, report.warn, report.fail
+        );
+    }
+
+    if report.fail > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+pub fn run_checks() -> Result<Vec<Check>> {
+    let home = home_dir().context("Failed to resolve HOME")?;
+    let store = profiles_root(&home);
+
+    let mut checks = vec![
+        check_waydroid(),
+        check_cfg(),
+        check_binder(),
+        check_wayland(),
+        check_dbus(),
+        check_store_writable(&store),
+        check_free_space(&store),
+        check_debugfs(),
+    ];
+
+    let broken = find_broken_image_links(&home.join("waydroid-images"));
+    checks.push(if broken.is_empty() {
+        Check::new("image links", Level::Pass, "no broken system.img/vendor.img links")
+    } else {
+        Check::new(
+            "image links",
+            Level::Warn,
+            format!(
+                "broken: {}",
+                broken
+                    .iter()
+                    .map(|p| p.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        )
+    });
+
+    let live_links = [
+        home.join(".local/share/waydroid/data"),
+        PathBuf::from(OVERLAY_RW_LIVE),
+        PathBuf::from(OVERLAY_WORK_LIVE),
+    ];
+    let dangling: Vec<String> = live_links
+        .iter()
+        .filter(|p| is_dangling_link(p))
+        .map(|p| p.display().to_string())
+        .collect();
+    checks.push(if dangling.is_empty() {
+        Check::new("live links", Level::Pass, "data and overlay links resolve")
+    } else {
+        Check::new(
+            "live links",
+            Level::Fail,
+            format!("dangling: {}", dangling.join(", ")),
+        )
+    });
+
+    Ok(checks)
+}
+
+fn check_waydroid() -> Check {
+    match run_cmd("waydroid", &["--version"]) {
+        Ok(version) => Check::new("waydroid", Level::Pass, format!("installed, {}", version)),
+        Err(err) => Check::new("waydroid", Level::Fail, format!("not usable: {}", err)),
+    }
+}
+
+fn check_debugfs() -> Check {
+    match run_cmd("debugfs", &["-V"]) {
+        Ok(_) => Check::new("debugfs", Level::Pass, "image build dates readable"),
+        Err(_) => Check::new(
+            "debugfs",
+            Level::Warn,
+            "not found (e2fsprogs), system_datetime/vendor_datetime are not synced on switch",
+        ),
+    }
+}
+
+fn check_cfg() -> Check {
+    let Ok(cfg) = fs::read_to_string(WAYDROID_CFG) else {
+        return Check::new(
+            "waydroid.cfg",
+            Level::Fail,
+            format!("{} missing, run `sudo waydroid init`", WAYDROID_CFG),
+        );
+    };
+    let has_section = cfg.lines().any(|l| l.trim() == "[waydroid]");
+    let has_images = cfg.lines().any(|l| l.starts_with("images_path ="));
+    match (has_section, has_images) {
+        (true, true) => Check::new("waydroid.cfg", Level::Pass, "initialised"),
+        (true, false) => Check::new("waydroid.cfg", Level::Fail, "images_path not set"),
+        _ => Check::new("waydroid.cfg", Level::Fail, "no [waydroid] section"),
+    }
+}
+
+fn check_binder() -> Check {
+    let devices = ["/dev/binderfs/binder", "/dev/binder", "/dev/anbox-binder"];
+    if let Some(dev) = devices.iter().find(|d| Path::new(d).exists()) {
+        return Check::new("binder", Level::Pass, format!("{} present", dev));
+    }
+    let supported = fs::read_to_string("/proc/filesystems")
+        .map(|fs| fs.lines().any(|l| l.trim_end().ends_with("binder")))
+        .unwrap_or(false);
+    if supported {
+        Check::new(
+            "binder",
+            Level::Warn,
+            "binderfs supported but not mounted yet (waydroid mounts it on container start)",
+        )
+    } else {
+        Check::new(
+            "binder",
+            Level::Fail,
+            "no binder device and binderfs not supported by this kernel",
+        )
+    }
+}
+
+fn check_wayland() -> Check {
+    let display = env::var("WAYLAND_DISPLAY").ok();
+    let runtime = env::var("XDG_RUNTIME_DIR").ok();
+    match (display, runtime) {
+        (Some(display), Some(runtime)) => {
+            let socket = Path::new(&runtime).join(&display);
+            if socket.exists() {
+                Check::new("wayland", Level::Pass, format!("{}", socket.display()))
+            } else {
+                Check::new(
+                    "wayland",
+                    Level::Warn,
+                    format!("{} set but {} missing", display, socket.display()),
+                )
+            }
+        }
+        (None, _) => Check::new(
+            "wayland",
+            Level::Warn,
+            "WAYLAND_DISPLAY not set, session start needs a Wayland session",
+        ),
+        (Some(_), None) => Check::new("wayland", Level::Warn, "XDG_RUNTIME_DIR not set"),
+    }
+}
+
+fn check_dbus() -> Check {
+    if let Ok(addr) = env::var("DBUS_SESSION_BUS_ADDRESS") {
+        return Check::new("dbus", Level::Pass, addr);
+    }
+    match env::var("XDG_RUNTIME_DIR") {
+        Ok(xdg) if Path::new(&xdg).join("bus").exists() => Check::new(
+            "dbus",
+            Level::Warn,
+            format!(
+                "DBUS_SESSION_BUS_ADDRESS not set, will fall back to unix:path={}/bus",
+                xdg
+            ),
+        ),
+        _ => Check::new(
+            "dbus",
+            Level::Fail,
+            "DBUS_SESSION_BUS_ADDRESS not set and no session bus under XDG_RUNTIME_DIR",
+        ),
+    }
+}
+
+fn check_store_writable(store: &Path) -> Check {
+    let probe = store.join(".doctor-probe");
+    let result = fs::create_dir_all(store)
+        .and_then(|_| fs::write(&probe, b"probe"))
+        .and_then(|_| fs::remove_file(&probe));
+    match result {
+        Ok(()) => Check::new("profile store", Level::Pass, format!("{} writable", store.display())),
+        Err(err) => Check::new(
+            "profile store",
+            Level::Fail,
+            format!("{} not writable: {}", store.display(), err),
+        ),
+    }
+}
+
+fn check_free_space(store: &Path) -> Check {
+    let Some(available_kb) = available_kb(store) else {
+        return Check::new("disk space", Level::Warn, "could not determine free space");
+    };
+    let detail = format!("{} MiB free on {}", available_kb / 1024, store.display());
+    let level = if available_kb < LOW_SPACE_FAIL_KB {
+        Level::Fail
+    } else if available_kb < LOW_SPACE_WARN_KB {
+        Level::Warn
+    } else {
+        Level::Pass
+    };
+    Check::new("disk space", level, detail)
+}
+
+fn available_kb(path: &Path) -> Option<u64> {
+    let path_s = path.to_string_lossy().to_string();
+    let out = run_cmd("df", &["-Pk", &path_s]).ok()?;
+    let line = out.lines().nth(1)?;
+    line.split_whitespace().nth(3)?.parse().ok()
+}
+
+/// `system.img`/`vendor.img` symlinks under `base` whose target is gone.
+pub fn find_broken_image_links(base: &Path) -> Vec<PathBuf> {
+    let mut out = Vec::new();
+    collect_broken_image_links(base, &mut out);
+    out.sort();
+    out
+}
+
+fn collect_broken_image_links(dir: &Path, out: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name();
+        if (name == "system.img" || name == "vendor.img") && is_dangling_link(&path) {
+            out.push(path);
+        } else if path.is_dir() {
+            collect_broken_image_links(&path, out);
+        }
+    }
+}
+
+pub fn is_dangling_link(path: &Path) -> bool {
+    fs::symlink_metadata(path)
+        .map(|m| m.file_type().is_symlink())
+        .unwrap_or(false)
+        && !path.exists()
+}
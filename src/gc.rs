@@ -0,0 +1,325 @@
+use anyhow::{bail, Context, Result};
+use dirs::home_dir;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use crate::{
+    consistency::Consistency, discover_profiles, disk_usage_kb, history, human_size_kb, lock,
+    profile_id_from_path, profile_ops::find_profile, profiles_root, run_cmd, session_left_stopped,
+    snapshot::stop_if_running, ImageProfile, STORE_DIRS,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OrphanKind {
+    /// A `profiles/<id>` store no scanned profile or live link uses.
+    Store,
+    /// `profiles/_legacy`, userdata from before the first switch.
+    Legacy,
+    /// `~/.local/share/waydroid/data.backup`.
+    DataBackup,
+    /// An overlay dir `repair` moved to `profiles/_recovered`.
+    Recovered,
+}
+
+/// What happens to store dirs the target profile already has files in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AttachMode {
+    /// Only fill empty dirs, refuse otherwise.
+    Move,
+    /// Exchange places: the profile's dir takes the orphan's spot.
+    Swap,
+    /// Copy the orphan over the profile's dir; the orphan is kept.
+    Merge,
+}
+
+/// Profile data nothing points at any more.
+#[derive(Clone, Debug)]
+pub struct Orphan {
+    /// Path relative to the profile store (or `data.backup`), used as id.
+    pub id: String,
+    pub path: PathBuf,
+    pub kind: OrphanKind,
+    pub kb: Option<u64>,
+    pub modified: Option<u64>,
+}
+
+impl Orphan {
+    fn new(id: String, path: PathBuf, kind: OrphanKind) -> Self {
+        let modified = fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs());
+        Self {
+            id,
+            kb: disk_usage_kb(&path),
+            modified,
+            path,
+            kind,
+        }
+    }
+
+    /// Store dirs this orphan can provide, as (store dir name, source path).
+    pub fn parts(&self) -> Vec<(&'static str, PathBuf)> {
+        match self.kind {
+            OrphanKind::DataBackup => vec![("data", self.path.clone())],
+            OrphanKind::Recovered => {
+                let name = self.path.file_name().unwrap_or_default().to_string_lossy();
+                STORE_DIRS
+                    .into_iter()
+                    .filter(|dir| name.starts_with(&format!("{}-", dir)))
+                    .map(|dir| (dir, self.path.clone()))
+                    .collect()
+            }
+            OrphanKind::Store | OrphanKind::Legacy => STORE_DIRS
+                .into_iter()
+                .map(|dir| (dir, self.path.join(dir)))
+                .filter(|(_, path)| path.exists())
+                .collect(),
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        let kind = match self.kind {
+            OrphanKind::Store => "orphaned store",
+            OrphanKind::Legacy => "pre-switcher userdata",
+            OrphanKind::DataBackup => "userdata backup",
+            OrphanKind::Recovered => "recovered overlay",
+        };
+        let parts: Vec<&str> = self.parts().into_iter().map(|(name, _)| name).collect();
+        format!(
+            "{:<36} {:>10}  {}  {} [{}]",
+            self.id,
+            self.kb
+                .map(human_size_kb)
+                .unwrap_or_else(|| "?".to_string()),
+            self.modified
+                .map(history::format_timestamp)
+                .unwrap_or_else(|| "?".to_string()),
+            kind,
+            if parts.is_empty() {
+                "empty".to_string()
+            } else {
+                parts.join(", ")
+            }
+        )
+    }
+}
+
+/// Everything under the profile store that neither a scanned profile nor
+/// the live links use, plus `data.backup`, largest first.
+pub fn find_orphans(profiles: &[ImageProfile]) -> Result<Vec<Orphan>> {
+    let home = home_dir().context("Failed to resolve HOME")?;
+    let root = profiles_root(&home);
+
+    let mut live: Vec<String> = profiles
+        .iter()
+        .map(|p| profile_id_from_path(&p.path, &home))
+        .collect();
+    live.extend(Consistency::check()?.profile_ids());
+
+    let mut orphans = Vec::new();
+    if let Ok(entries) = fs::read_dir(&root) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !path.is_dir() || live.contains(&name) {
+                continue;
+            }
+            match name.as_str() {
+                "_legacy" => orphans.push(Orphan::new(name, path, OrphanKind::Legacy)),
+                "_recovered" => {
+                    for rescued in fs::read_dir(&path).into_iter().flatten().flatten() {
+                        orphans.push(Orphan::new(
+                            format!("_recovered/{}", rescued.file_name().to_string_lossy()),
+                            rescued.path(),
+                            OrphanKind::Recovered,
+                        ));
+                    }
+                }
+                _ => orphans.push(Orphan::new(name, path, OrphanKind::Store)),
+            }
+        }
+    }
+
+    let backup = home.join(".local/share/waydroid/data.backup");
+    if backup.is_dir() {
+        orphans.push(Orphan::new(
+            "data.backup".to_string(),
+            backup,
+            OrphanKind::DataBackup,
+        ));
+    }
+
+    orphans.sort_by(|a, b| b.kb.cmp(&a.kb).then_with(|| a.id.cmp(&b.id)));
+    Ok(orphans)
+}
+
+pub fn remove(orphan: &Orphan) -> Result<Vec<String>> {
+    let _lock = lock::acquire("gc")?;
+    let path_s = orphan.path.to_string_lossy().to_string();
+    run_cmd("sudo", &["rm", "-rf", &path_s])?;
+    let mut logs = vec![format!("gc: removed {}", orphan.path.display())];
+    if orphan.kind == OrphanKind::Recovered {
+        if let Some(parent) = orphan.path.parent() {
+            if fs::remove_dir(parent).is_ok() {
+                logs.push(format!("gc: removed empty {}", parent.display()));
+            }
+        }
+    }
+    Ok(logs)
+}
+
+/// Adopts the orphan's dirs as `profile`'s userdata/overlays. Empty store
+/// dirs are simply replaced; `mode` decides about ones holding files.
+pub fn attach(orphan: &Orphan, profile: &ImageProfile, mode: AttachMode) -> Result<Vec<String>> {
+    let _lock = lock::acquire("gc")?;
+    let home = home_dir().context("Failed to resolve HOME")?;
+    let store = profiles_root(&home).join(profile_id_from_path(&profile.path, &home));
+    let parts = orphan.parts();
+    if parts.is_empty() {
+        bail!("{} holds no userdata or overlays", orphan.id);
+    }
+    let occupied = |name: &str| !is_effectively_empty(&store.join(name));
+    if mode == AttachMode::Move {
+        if let Some((name, _)) = parts.iter().find(|(name, _)| occupied(name)) {
+            bail!(
+                "'{}' already has {} in {}; swap or merge instead",
+                profile.name,
+                name,
+                store.display()
+            );
+        }
+    }
+
+    let mut logs = Vec::new();
+    let active = stop_if_running(profile, &mut logs);
+    fs::create_dir_all(&store).with_context(|| format!("Failed creating {}", store.display()))?;
+    for (name, source) in &parts {
+        let target = store.join(name);
+        let target_s = target.to_string_lossy().to_string();
+        let source_s = source.to_string_lossy().to_string();
+        match mode {
+            AttachMode::Swap if occupied(name) => {
+                let parked = store.join(format!("{}.swap", name));
+                let parked_s = parked.to_string_lossy().to_string();
+                run_cmd("sudo", &["mv", &target_s, &parked_s])?;
+                run_cmd("sudo", &["mv", &source_s, &target_s])?;
+                run_cmd("sudo", &["mv", &parked_s, &source_s])?;
+                logs.push(format!(
+                    "gc: swapped {} <-> {}",
+                    source.display(),
+                    target.display()
+                ));
+            }
+            AttachMode::Merge if occupied(name) => {
+                let contents = format!("{}/.", source_s);
+                run_cmd(
+                    "sudo",
+                    &["cp", "-a", "--reflink=auto", &contents, &target_s],
+                )?;
+                logs.push(format!(
+                    "gc: merged {} into {}",
+                    source.display(),
+                    target.display()
+                ));
+            }
+            _ => {
+                run_cmd("sudo", &["rm", "-rf", &target_s])?;
+                run_cmd("sudo", &["mv", &source_s, &target_s])?;
+                logs.push(format!(
+                    "gc: moved {} -> {}",
+                    source.display(),
+                    target.display()
+                ));
+            }
+        }
+    }
+    if mode == AttachMode::Merge && orphan.path.exists() {
+        logs.push(format!(
+            "gc: {} kept, delete it once the merge looks right",
+            orphan.path.display()
+        ));
+    } else if orphan.path.exists() && fs::remove_dir(&orphan.path).is_ok() {
+        logs.push(format!("gc: removed empty {}", orphan.path.display()));
+    }
+    if active {
+        logs.push(session_left_stopped("gc"));
+    }
+    Ok(logs)
+}
+
+/// `waydroid-switch gc [delete <ID>... | attach [--swap|--merge] <ID> <PROFILE>] [-y]`
+pub fn run(args: &[String]) -> Result<()> {
+    let yes = args.iter().any(|a| a == "-y" || a == "--yes");
+    let mode = if args.iter().any(|a| a == "--swap") {
+        AttachMode::Swap
+    } else if args.iter().any(|a| a == "--merge") {
+        AttachMode::Merge
+    } else {
+        AttachMode::Move
+    };
+    let rest: Vec<&str> = args
+        .iter()
+        .map(String::as_str)
+        .filter(|a| !a.starts_with('-'))
+        .collect();
+    let orphans = find_orphans(&discover_profiles()?)?;
+    let find = |id: &str| {
+        orphans
+            .iter()
+            .find(|o| o.id == id)
+            .with_context(|| format!("No orphaned data '{}'; run 'waydroid-switch gc'", id))
+    };
+
+    match rest.as_slice() {
+        [] => {
+            if orphans.is_empty() {
+                println!("No orphaned profile data");
+            }
+            for orphan in &orphans {
+                println!("{}", orphan.summary());
+            }
+        }
+        ["delete", ids @ ..] if !ids.is_empty() => {
+            let chosen = ids.iter().map(|id| find(id)).collect::<Result<Vec<_>>>()?;
+            for orphan in chosen {
+                println!("{}", orphan.summary());
+                if !yes && !crate::prompt_yes_no("Delete it?")? {
+                    continue;
+                }
+                println!("{}", remove(orphan)?.join("\n"));
+            }
+        }
+        ["attach", id, profile] => {
+            let orphan = find(id)?;
+            let profile = find_profile(profile)?;
+            println!("{}", orphan.summary());
+            let question = format!("Attach it to '{}'?", profile.name);
+            if !yes && !crate::prompt_yes_no(&question)? {
+                bail!("Attach cancelled");
+            }
+            println!("{}", attach(orphan, &profile, mode)?.join("\n"));
+        }
+        _ => bail!("gc expects no arguments, 'delete <ID>...' or 'attach <ID> <PROFILE>'"),
+    }
+    Ok(())
+}
+
+/// No files below `dir` (the empty `system`/`vendor` overlay subdirs do
+/// not count). Unreadable dirs count as not empty.
+fn is_effectively_empty(dir: &Path) -> bool {
+    if !dir.exists() {
+        return true;
+    }
+    let Ok(entries) = fs::read_dir(dir) else {
+        return false;
+    };
+    entries.flatten().all(|entry| {
+        let path = entry.path();
+        path.is_dir() && !path.is_symlink() && is_effectively_empty(&path)
+    })
+}
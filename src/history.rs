@@ -0,0 +1,172 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::app_state_dir;
+
+/// One line of the append-only switch log.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SwitchRecord {
+    pub timestamp: u64,
+    pub from: Option<String>,
+    pub to: String,
+    pub user: String,
+    pub outcome: String,
+    pub logs: Vec<String>,
+}
+
+impl SwitchRecord {
+    pub fn new(from: Option<String>, to: String, outcome: String, logs: Vec<String>) -> Self {
+        Self {
+            timestamp: unix_now(),
+            from,
+            to,
+            user: invoking_user(),
+            outcome,
+            logs,
+        }
+    }
+
+    pub fn succeeded(&self) -> bool {
+        self.outcome == "ok"
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "{}  {}  {} -> {}  [{}]",
+            format_timestamp(self.timestamp),
+            self.user,
+            self.from.as_deref().unwrap_or("(unknown)"),
+            self.to,
+            self.outcome
+        )
+    }
+}
+
+pub fn history_path() -> Result<PathBuf> {
+    Ok(app_state_dir()?.join("history.jsonl"))
+}
+
+pub fn append(record: &SwitchRecord) -> Result<()> {
+    let path = history_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed creating {}", parent.display()))?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed opening {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(record)?)
+        .with_context(|| format!("Failed writing {}", path.display()))?;
+    Ok(())
+}
+
+/// All records, oldest first. Lines that do not parse are skipped so a torn
+/// write does not hide the rest of the log.
+pub fn load() -> Result<Vec<SwitchRecord>> {
+    let path = history_path()?;
+    let Ok(raw) = fs::read_to_string(&path) else {
+        return Ok(Vec::new());
+    };
+    Ok(raw
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// `waydroid-switch history [--json] [-n COUNT] [-v]`, newest first.
+pub fn run(args: &[String]) -> Result<()> {
+    let json = args.iter().any(|a| a == "--json");
+    let verbose = args.iter().any(|a| a == "-v" || a == "--verbose");
+    let limit = args
+        .iter()
+        .position(|a| a == "-n")
+        .and_then(|i| args.get(i + 1))
+        .map(|n| n.parse::<usize>().context("-n expects a number"))
+        .transpose()?;
+
+    let mut records = load()?;
+    records.reverse();
+    if let Some(limit) = limit {
+        records.truncate(limit);
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&records)?);
+        return Ok(());
+    }
+    if records.is_empty() {
+        println!("No switches recorded in {}", history_path()?.display());
+        return Ok(());
+    }
+    for record in &records {
+        println!("{}", record.summary());
+        if verbose {
+            for line in &record.logs {
+                println!("    {}", line);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn invoking_user() -> String {
+    env::var("SUDO_USER")
+        .or_else(|_| env::var("USER"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// `YYYY-MM-DD HH:MM:SS UTC` without pulling in a date crate.
+pub fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+
+    // Civil-from-days, Howard Hinnant's algorithm.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_timestamp_renders_utc_dates() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00:00 UTC");
+        assert_eq!(format_timestamp(1_700_000_000), "2023-11-14 22:13:20 UTC");
+        assert_eq!(format_timestamp(4_102_444_799), "2099-12-31 23:59:59 UTC");
+    }
+}
@@ -0,0 +1,119 @@
+use anyhow::{bail, Context, Result};
+use std::{
+    env,
+    fs::{self, File, OpenOptions, TryLockError},
+    io::{Read, Seek, SeekFrom, Write},
+    os::unix::fs::{OpenOptionsExt, PermissionsExt},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+const LOCK_FILE: &str = "waydroid-switch.lock";
+
+/// The open lock file and how many guards in this process share it, so an
+/// operation that switches as one of its steps does not block on itself.
+static HELD: Mutex<Option<(File, usize)>> = Mutex::new(None);
+
+/// Advisory lock shared by every `waydroid-switch` process on the machine.
+/// Released when the last guard in this process is dropped.
+#[must_use]
+pub struct OpLock {
+    _private: (),
+}
+
+impl Drop for OpLock {
+    fn drop(&mut self) {
+        let mut held = HELD.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((_, depth)) = held.as_mut() {
+            *depth -= 1;
+            if *depth == 0 {
+                *held = None;
+            }
+        }
+    }
+}
+
+/// `/run/lock` where every user can create files there, `/tmp` otherwise.
+/// Never `$TMPDIR`: processes with different environments (per-user tmp
+/// dirs, sudo) must still end up on the same file.
+pub fn lock_path() -> PathBuf {
+    let shared = Path::new("/run/lock");
+    let dir = match fs::metadata(shared) {
+        Ok(meta) if meta.is_dir() && meta.permissions().mode() & 0o002 != 0 => shared,
+        _ => Path::new("/tmp"),
+    };
+    dir.join(LOCK_FILE)
+}
+
+/// Takes the lock for `operation` (e.g. "switch"), failing immediately with
+/// the holder's pid and operation when another process has it.
+pub fn acquire(operation: &str) -> Result<OpLock> {
+    let mut held = HELD.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((_, depth)) = held.as_mut() {
+        *depth += 1;
+        return Ok(OpLock { _private: () });
+    }
+
+    let path = lock_path();
+    let mut file = open_lock_file(&path)?;
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => {
+            let mut holder = String::new();
+            let _ = file.read_to_string(&mut holder);
+            let holder = holder.trim();
+            if holder.is_empty() {
+                bail!("Another waydroid-switch is busy (lock {})", path.display());
+            }
+            bail!(
+                "Another waydroid-switch is busy: {} (lock {})",
+                holder,
+                path.display()
+            );
+        }
+        Err(TryLockError::Error(err)) => {
+            return Err(err).with_context(|| format!("Failed locking {}", path.display()));
+        }
+    }
+
+    // Best effort: a read-only handle created by another user still locks.
+    if file.set_len(0).is_ok() && file.seek(SeekFrom::Start(0)).is_ok() {
+        let _ = writeln!(
+            file,
+            "pid {} ({}) by {}",
+            std::process::id(),
+            operation,
+            env::var("USER").unwrap_or_else(|_| "unknown".to_string())
+        );
+    }
+
+    *held = Some((file, 1));
+    Ok(OpLock { _private: () })
+}
+
+fn open_lock_file(path: &PathBuf) -> Result<File> {
+    // The directory is world-writable, so a planted link could make a
+    // privileged run truncate whatever it points to.
+    if fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_symlink()) {
+        bail!("Lock file {} is a symlink, remove it first", path.display());
+    }
+    // Open before creating: sticky temp dirs may refuse O_CREAT on a file
+    // owned by another user.
+    if let Ok(file) = OpenOptions::new().read(true).write(true).open(path) {
+        return Ok(file);
+    }
+    if let Ok(file) = File::open(path) {
+        return Ok(file);
+    }
+
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .mode(0o666)
+        .open(path)
+        .with_context(|| format!("Failed creating lock file {}", path.display()))?;
+    let _ = fs::set_permissions(path, fs::Permissions::from_mode(0o666));
+    Ok(file)
+}
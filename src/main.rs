@@ -1,4 +1,38 @@
+mod build_date;
+mod bundle;
+mod cfg;
+mod config;
+mod consistency;
+mod details;
+mod doctor;
+mod gc;
+mod history;
+mod lock;
+mod overrides;
+mod profile_ops;
+mod props;
+mod repair;
+mod search;
+mod snapshot;
+mod state;
+mod tree;
+mod uninstall;
+mod usage;
+
 use anyhow::{bail, Context, Result};
+use cfg::Ini;
+use config::{Config, UpgradeMode};
+use consistency::Consistency;
+use details::{DirSize, ProfileDetails, SizeCache};
+use gc::{AttachMode, Orphan};
+use history::SwitchRecord;
+use profile_ops::DeletePlan;
+use props::{Prop, PropSource, PropsLoader};
+use search::{MatchField, ProfileMatch};
+use snapshot::Snapshot;
+use state::SwitchState;
+use tree::TreeRow;
+use usage::UsageReport;
 use crossterm::{
     event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
     execute,
@@ -7,11 +41,11 @@ use crossterm::{
 use dirs::home_dir;
 use ratatui::{
     prelude::*,
-    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
 };
 use std::{
     collections::hash_map::DefaultHasher,
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet, HashMap},
     hash::{Hash, Hasher},
     fs,
     io,
@@ -23,11 +57,68 @@ use std::{
 
 const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
 const WAYDROID_CFG: &str = "/var/lib/waydroid/waydroid.cfg";
+const OVERLAY_RW_LIVE: &str = "/var/lib/waydroid/overlay_rw";
+const OVERLAY_WORK_LIVE: &str = "/var/lib/waydroid/overlay_work";
+/// What a profile store under `~/.local/share/waydroid/profiles/<id>` holds.
+const STORE_DIRS: [&str; 3] = ["data", "overlay_rw", "overlay_work"];
+/// tar flags that keep owners, permissions and xattrs (SELinux labels) of
+/// a store intact.
+const TAR_PRESERVE: [&str; 3] = ["--xattrs", "--xattrs-include=*", "--numeric-owner"];
+const PROFILE_META_FILE: &str = "profile.cfg";
 
 #[derive(Clone, Debug)]
 struct ImageProfile {
     name: String,
     path: PathBuf,
+    meta: ProfileMeta,
+}
+
+/// Optional `profile.cfg` in a profile folder. `[meta]` holds `tags` plus any
+/// free-form build information (`build = lineage-20.0-20240101`, ...).
+/// `[waydroid]` and `[properties]` are applied to waydroid.cfg on switch.
+#[derive(Clone, Debug, Default)]
+struct ProfileMeta {
+    tags: Vec<String>,
+    fields: Vec<(String, String)>,
+    /// (section, key, value) for waydroid.cfg.
+    cfg_overrides: Vec<(String, String, String)>,
+}
+
+impl ProfileMeta {
+    fn load(dir: &Path) -> Self {
+        let Ok(ini) = Ini::load(&dir.join(PROFILE_META_FILE)) else {
+            return Self::default();
+        };
+        let mut meta = Self::default();
+        for (key, value) in ini.section("meta") {
+            if key == "tags" {
+                meta.tags = value
+                    .split(',')
+                    .map(|t| t.trim().to_string())
+                    .filter(|t| !t.is_empty())
+                    .collect();
+            } else {
+                meta.fields.push((key, value));
+            }
+        }
+        for section in overrides::SECTIONS {
+            for (key, value) in ini.section(section) {
+                meta.cfg_overrides.push((section.to_string(), key, value));
+            }
+        }
+        meta
+    }
+
+    fn search_text(&self) -> String {
+        let mut parts = Vec::new();
+        if !self.tags.is_empty() {
+            parts.push(format!("tags: {}", self.tags.join(", ")));
+        }
+        for (key, value) in &self.fields {
+            parts.push(format!("{}: {}", key, value));
+        }
+        parts.join("  ")
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -46,30 +137,44 @@ impl Field {
         }
     }
 
+    /// Pre-filled field with the cursor at the end.
+    fn with_value(label: &'static str, value: String) -> Self {
+        Self {
+            label,
+            cursor: value.len(),
+            value,
+        }
+    }
+
     fn insert_char(&mut self, c: char) {
         self.value.insert(self.cursor, c);
         self.cursor += c.len_utf8();
     }
 
+    // `cursor` is a byte offset, so it steps over whole chars.
     fn backspace(&mut self) {
-        if self.cursor == 0 {
-            return;
+        if let Some(c) = self.value[..self.cursor].chars().next_back() {
+            self.cursor -= c.len_utf8();
+            self.value.remove(self.cursor);
         }
-        self.cursor -= 1;
-        self.value.remove(self.cursor);
     }
 
     fn move_left(&mut self) {
-        if self.cursor > 0 {
-            self.cursor -= 1;
+        if let Some(c) = self.value[..self.cursor].chars().next_back() {
+            self.cursor -= c.len_utf8();
         }
     }
 
     fn move_right(&mut self) {
-        if self.cursor < self.value.len() {
-            self.cursor += 1;
+        if let Some(c) = self.value[self.cursor..].chars().next() {
+            self.cursor += c.len_utf8();
         }
     }
+
+    /// Terminal column of the cursor relative to the start of the value.
+    fn cursor_col(&self) -> u16 {
+        self.value[..self.cursor].chars().count() as u16
+    }
 }
 
 #[derive(Debug)]
@@ -111,6 +216,47 @@ impl ManualAddState {
 enum Screen {
     Profiles,
     ManualAdd,
+    Reconcile,
+    History,
+    Confirm,
+    Delete,
+    Rename,
+    Clone,
+    Snapshots,
+    Reset,
+    Usage,
+    Gc,
+    Props,
+}
+
+/// Text input in progress on the properties screen.
+#[derive(Clone, Debug)]
+enum PropEdit {
+    /// Typing the key of a new property.
+    NewKey,
+    /// Typing the value for `key`.
+    Value { key: String, source: PropSource },
+}
+
+/// Orphan action waiting for `y` on the GC screen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum GcAction {
+    Delete,
+    Attach(AttachMode),
+}
+
+/// Destructive snapshot action waiting for `y` on the snapshots screen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SnapshotAction {
+    Restore,
+    Delete,
+}
+
+/// A switch waiting for the user to confirm it in the modal.
+#[derive(Clone, Debug)]
+struct PendingSwitch {
+    target: ImageProfile,
+    session_running: Option<bool>,
 }
 
 #[derive(Debug)]
@@ -121,13 +267,304 @@ struct App {
     current_images_path: Option<String>,
     status: String,
     manual: ManualAddState,
+    consistency: Option<Consistency>,
+    reconcile_selected: usize,
+    history: Vec<SwitchRecord>,
+    history_selected: usize,
+    filter: String,
+    filter_editing: bool,
+    tree_mode: bool,
+    tree_cursor: usize,
+    collapsed: BTreeSet<String>,
+    details: Option<ProfileDetails>,
+    sizes: SizeCache,
+    last_used: HashMap<String, u64>,
+    config: Config,
+    pending: Option<PendingSwitch>,
+    delete_plan: Option<DeletePlan>,
+    delete_with_store: bool,
+    rename: Field,
+    clone_name: Field,
+    clone_copy_images: bool,
+    snapshots: Vec<Snapshot>,
+    snapshot_selected: usize,
+    snapshot_naming: bool,
+    snapshot_name: Field,
+    snapshot_pending: Option<SnapshotAction>,
+    reset_data: bool,
+    reset_overlays: bool,
+    usage: Option<UsageReport>,
+    usage_selected: usize,
+    orphans: Vec<Orphan>,
+    orphan_selected: usize,
+    gc_target: usize,
+    gc_pending: Option<GcAction>,
+    props: Vec<Prop>,
+    props_loader: PropsLoader,
+    /// A reload is running on the loader; `props` still holds the last one.
+    props_loading: bool,
+    prop_selected: usize,
+    prop_edit: Option<PropEdit>,
+    prop_field: Field,
+    prop_new_source: PropSource,
+    prop_removing: bool,
+}
+
+impl App {
+    /// Indices into `profiles` shown in the list, with the search match that
+    /// let each one through.
+    fn visible_profiles(&self) -> Vec<(usize, Option<ProfileMatch>)> {
+        if self.filter.is_empty() {
+            return (0..self.profiles.len()).map(|i| (i, None)).collect();
+        }
+        self.profiles
+            .iter()
+            .enumerate()
+            .filter_map(|(i, p)| search::match_profile(&self.filter, p).map(|m| (i, Some(m))))
+            .collect()
+    }
+
+    fn move_selection(&mut self, up: bool) {
+        let visible = self.visible_profiles();
+        let Some(pos) = visible.iter().position(|(i, _)| *i == self.selected) else {
+            self.snap_selection_to_filter();
+            return;
+        };
+        let next = if up {
+            pos.checked_sub(1)
+        } else {
+            Some(pos + 1).filter(|n| *n < visible.len())
+        };
+        if let Some(next) = next {
+            self.selected = visible[next].0;
+        }
+    }
+
+    fn snap_selection_to_filter(&mut self) {
+        let visible = self.visible_profiles();
+        if !visible.iter().any(|(i, _)| *i == self.selected) {
+            if let Some((first, _)) = visible.first() {
+                self.selected = *first;
+            }
+        }
+        if self.tree_mode {
+            self.sync_tree_cursor();
+        }
+    }
+
+    /// Folder tree of the visible profiles. Folders are all expanded while a
+    /// search is active so every match can be seen.
+    fn tree_rows(&self) -> Vec<TreeRow> {
+        let visible: Vec<usize> = self.visible_profiles().into_iter().map(|(i, _)| i).collect();
+        if self.filter.is_empty() {
+            tree::build_rows(&self.profiles, &visible, &self.collapsed)
+        } else {
+            tree::build_rows(&self.profiles, &visible, &BTreeSet::new())
+        }
+    }
+
+    fn sync_tree_cursor(&mut self) {
+        let rows = self.tree_rows();
+        if let Some(pos) = rows.iter().position(|r| r.profile == Some(self.selected)) {
+            self.tree_cursor = pos;
+        } else {
+            self.tree_cursor = self.tree_cursor.min(rows.len().saturating_sub(1));
+        }
+    }
+
+    fn set_tree_cursor(&mut self, rows: &[TreeRow], cursor: usize) {
+        self.tree_cursor = cursor;
+        if let Some(idx) = rows.get(cursor).and_then(|r| r.profile) {
+            self.selected = idx;
+        }
+    }
+
+    fn set_folder_expanded(&mut self, folder: &str, expanded: bool) {
+        if expanded {
+            self.collapsed.remove(folder);
+        } else {
+            self.collapsed.insert(folder.to_string());
+        }
+        let rows = self.tree_rows();
+        self.tree_cursor = rows
+            .iter()
+            .position(|r| r.folder.as_deref() == Some(folder))
+            .unwrap_or(0);
+    }
+
+    /// Reloads the snapshots of the selected profile.
+    fn reload_snapshots(&mut self) {
+        match self.profiles.get(self.selected).map(snapshot::list) {
+            Some(Ok(snapshots)) => self.snapshots = snapshots,
+            Some(Err(e)) => {
+                self.snapshots.clear();
+                self.status = format!("Snapshot list failed: {}", e);
+            }
+            None => self.snapshots.clear(),
+        }
+        self.snapshot_selected = self
+            .snapshot_selected
+            .min(self.snapshots.len().saturating_sub(1));
+    }
+
+    fn reload_orphans(&mut self) {
+        match gc::find_orphans(&self.profiles) {
+            Ok(orphans) => {
+                self.status = format!("{} orphaned entries", orphans.len());
+                self.orphans = orphans;
+            }
+            Err(e) => {
+                self.orphans.clear();
+                self.status = format!("Orphan scan failed: {}", e);
+            }
+        }
+        self.orphan_selected = self
+            .orphan_selected
+            .min(self.orphans.len().saturating_sub(1));
+    }
+
+    fn reload_props(&mut self) {
+        self.props_loader.request();
+        self.props_loading = true;
+        // Edits may have gone into the active profile's profile.cfg.
+        if let Some(active) = self.current_images_path.as_deref() {
+            for profile in &mut self.profiles {
+                if profile.path == Path::new(active) {
+                    profile.meta = ProfileMeta::load(&profile.path);
+                }
+            }
+        }
+        self.details = None;
+    }
+
+    /// Takes over a finished props load.
+    fn poll_props(&mut self) {
+        let Some(result) = self.props_loader.poll() else {
+            return;
+        };
+        self.props_loading = false;
+        match result {
+            Ok(props) => self.props = props,
+            Err(e) => {
+                self.props.clear();
+                self.status = format!("Reading properties failed: {}", e);
+            }
+        }
+        self.prop_selected = self.prop_selected.min(self.props.len().saturating_sub(1));
+    }
+
+    fn reload_usage(&mut self) {
+        match UsageReport::collect(&self.profiles) {
+            Ok(report) => {
+                self.usage_selected = self
+                    .usage_selected
+                    .min(report.profiles.len().saturating_sub(1));
+                self.usage = Some(report);
+            }
+            Err(e) => {
+                self.usage = None;
+                self.status = format!("Disk usage failed: {}", e);
+            }
+        }
+    }
+
+    /// Recollects the details pane when the selection moved to another
+    /// profile or the cache was dropped after a switch. A dropped cache also
+    /// invalidates the measured sizes and last-used times.
+    fn refresh_details(&mut self) {
+        let Some(profile) = self.profiles.get(self.selected) else {
+            self.details = None;
+            return;
+        };
+        if self.sizes.poll() {
+            if let Some(details) = self.details.as_mut() {
+                details.fill_sizes(&mut self.sizes);
+            }
+        }
+        if self.details.as_ref().map(|d| &d.path) == Some(&profile.path) {
+            return;
+        }
+        if self.details.is_none() {
+            self.sizes.clear();
+            self.last_used = details::last_used_times();
+        }
+        let last_used = self.last_used.get(&profile.path.display().to_string()).copied();
+        self.details = ProfileDetails::collect(profile, last_used).ok();
+        if let Some(details) = self.details.as_mut() {
+            details.fill_sizes(&mut self.sizes);
+        }
+    }
+
+    fn recheck_consistency(&mut self) {
+        self.consistency = Consistency::check().ok().filter(|c| !c.is_consistent());
+        self.reconcile_selected = 0;
+    }
 }
 
+const USAGE: &str = "Usage: waydroid-switch [COMMAND]
+
+Without a command the interactive TUI is started.
+
+Commands:
+  switch [-y] <NAME|PATH|->
+                    Switch to a profile; '-' returns to the previous one,
+                    -y skips the confirmation
+  delete [--with-data] [-y] <NAME>
+                    Delete a profile, optionally with its userdata and overlays
+  rename <OLD> <NEW>
+                    Rename or move a profile, keeping its userdata and overlays
+  clone [--copy-images] <SOURCE> <NEW>
+                    Duplicate a profile with a copy of its userdata and overlays
+  reset [--data] [--overlays] [-y] <NAME>
+                    Wipe a profile's userdata and/or overlays (default both)
+  snapshot create <NAME> [LABEL]
+  snapshot list <NAME>
+  snapshot restore|delete [-y] <NAME> <SNAPSHOT>
+                    Save, list and restore a profile's userdata and overlays
+  export [--with-images] <NAME> [FILE]
+                    Pack a profile, its userdata and overlays into a bundle
+  import [--images DIR] <FILE> [NAME]
+                    Recreate a profile from a bundle
+  gc [delete <ID>... | attach <ID> <PROFILE>] [-y]
+                    List orphaned profile data, delete it or attach it to a profile
+  du [--json]       Disk usage per profile and totals, largest first
+  uninstall [--profile NAME] [--copy] [--archive] [-y]
+                    Restore the stock Waydroid layout from one profile
+  doctor [--json]   Check the environment switching depends on
+  repair [--dry-run]
+                    Find and fix half-switched or dangling state
+  history [--json] [-n COUNT] [-v]
+                    Show recorded switches, newest first";
+
 fn main() -> Result<()> {
-    if std::env::args().any(|a| a == "--version" || a == "-V") {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|a| a == "--version" || a == "-V") {
         println!("waydroid-switch {}", APP_VERSION);
         return Ok(());
     }
+    if args.iter().any(|a| a == "--help" || a == "-h") {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+
+    match args.first().map(String::as_str) {
+        Some("doctor") => return doctor::run(&args[1..]),
+        Some("repair") => return repair::run(&args[1..]),
+        Some("history") => return history::run(&args[1..]),
+        Some("switch") => return cli_switch(&args[1..]),
+        Some("delete") => return profile_ops::run_delete(&args[1..]),
+        Some("rename") => return profile_ops::run_rename(&args[1..]),
+        Some("clone") => return profile_ops::run_clone(&args[1..]),
+        Some("reset") => return profile_ops::run_reset(&args[1..]),
+        Some("snapshot") => return snapshot::run(&args[1..]),
+        Some("du") => return usage::run(&args[1..]),
+        Some("gc") => return gc::run(&args[1..]),
+        Some("uninstall") => return uninstall::run(&args[1..]),
+        Some("export") => return bundle::run_export(&args[1..]),
+        Some("import") => return bundle::run_import(&args[1..]),
+        Some(other) => bail!("Unknown command '{}'\n\n{}", other, USAGE),
+        None => {}
+    }
 
     let profiles = discover_profiles()?;
     if profiles.is_empty() {
@@ -153,7 +590,51 @@ fn main() -> Result<()> {
         current_images_path,
         status: "Auto-scan loaded. Enter=switch, a=manual add, r=refresh, q=quit".to_string(),
         manual: ManualAddState::new(),
+        consistency: None,
+        reconcile_selected: 0,
+        history: Vec::new(),
+        history_selected: 0,
+        filter: String::new(),
+        filter_editing: false,
+        tree_mode: false,
+        tree_cursor: 0,
+        collapsed: BTreeSet::new(),
+        details: None,
+        sizes: SizeCache::spawn(),
+        last_used: details::last_used_times(),
+        config: Config::load(),
+        pending: None,
+        delete_plan: None,
+        delete_with_store: false,
+        rename: Field::new("New name"),
+        clone_name: Field::new("New name"),
+        clone_copy_images: false,
+        snapshots: Vec::new(),
+        snapshot_selected: 0,
+        snapshot_naming: false,
+        snapshot_name: Field::new("Snapshot name"),
+        snapshot_pending: None,
+        reset_data: true,
+        reset_overlays: true,
+        usage: None,
+        usage_selected: 0,
+        orphans: Vec::new(),
+        orphan_selected: 0,
+        gc_target: 0,
+        gc_pending: None,
+        props: Vec::new(),
+        props_loader: PropsLoader::spawn(),
+        props_loading: false,
+        prop_selected: 0,
+        prop_edit: None,
+        prop_field: Field::new("Key"),
+        prop_new_source: PropSource::Cfg,
+        prop_removing: false,
     };
+    app.recheck_consistency();
+    if app.consistency.is_some() {
+        app.status = "Live state points at different profiles. Press c to reconcile".to_string();
+    }
 
     let mut terminal = init_terminal()?;
     let ui_result = run_ui(&mut terminal, &mut app);
@@ -161,8 +642,65 @@ fn main() -> Result<()> {
     ui_result
 }
 
+fn cli_switch(args: &[String]) -> Result<()> {
+    let yes = args.iter().any(|a| a == "-y" || a == "--yes");
+    let Some(target) = args.iter().find(|a| !a.starts_with("--") && *a != "-y") else {
+        bail!("switch expects a profile name, path or '-'\n\n{}", USAGE);
+    };
+    let path = if target == "-" {
+        SwitchState::load()
+            .previous
+            .map(PathBuf::from)
+            .context("No previous profile recorded yet")?
+    } else {
+        resolve_profile(target)?
+    };
+
+    if !yes && Config::load().confirm_switch {
+        let session = match session_running() {
+            Some(true) => "running, it will be stopped",
+            Some(false) => "stopped",
+            None => "unknown",
+        };
+        println!(
+            "From:    {}\nTo:      {}\nSession: {}",
+            current_images_path().unwrap_or_else(|_| "(unknown)".to_string()),
+            path.display(),
+            session
+        );
+        if !prompt_yes_no("Switch now?")? {
+            bail!("Switch cancelled");
+        }
+    }
+
+    println!("Switching to {}", path.display());
+    let logs = switch_to_profile(&path)?;
+    println!("{}", logs.join("\n"));
+    Ok(())
+}
+
+/// Looks `target` up by scanned profile name first, then as a folder path.
+fn resolve_profile(target: &str) -> Result<PathBuf> {
+    if let Some(profile) = discover_profiles()?.into_iter().find(|p| p.name == target) {
+        return Ok(profile.path);
+    }
+    let path = PathBuf::from(target);
+    if path.is_dir() {
+        return fs::canonicalize(&path)
+            .with_context(|| format!("Failed to resolve {}", path.display()));
+    }
+    bail!("No profile named '{}' in ~/waydroid-images", target)
+}
+
 fn run_ui(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App) -> Result<()> {
     loop {
+        // The reset modal shows sizes from the details pane too.
+        if matches!(app.screen, Screen::Profiles | Screen::Reset) {
+            app.refresh_details();
+        }
+        if app.screen == Screen::Props {
+            app.poll_props();
+        }
         terminal.draw(|f| draw(f, app))?;
 
         if !event::poll(Duration::from_millis(150))? {
@@ -178,6 +716,17 @@ fn run_ui(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App)
             match app.screen {
                 Screen::Profiles => handle_profiles_key(app, key, terminal)?,
                 Screen::ManualAdd => handle_manual_key(app, key)?,
+                Screen::Reconcile => handle_reconcile_key(app, key, terminal)?,
+                Screen::History => handle_history_key(app, key),
+                Screen::Confirm => handle_confirm_key(app, key, terminal)?,
+                Screen::Delete => handle_delete_key(app, key)?,
+                Screen::Rename => handle_rename_key(app, key)?,
+                Screen::Clone => handle_clone_key(app, key)?,
+                Screen::Snapshots => handle_snapshots_key(app, key)?,
+                Screen::Reset => handle_reset_key(app, key),
+                Screen::Usage => handle_usage_key(app, key),
+                Screen::Gc => handle_gc_key(app, key)?,
+                Screen::Props => handle_props_key(app, key),
             }
         }
     }
@@ -188,133 +737,868 @@ fn handle_profiles_key(
     key: KeyEvent,
     terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
 ) -> Result<()> {
+    if app.filter_editing {
+        handle_search_key(app, key);
+        return Ok(());
+    }
+
+    if app.tree_mode && handle_tree_key(app, key, terminal)? {
+        return Ok(());
+    }
+
     match key.code {
         KeyCode::Char('q') => std::process::exit(0),
-        KeyCode::Up => {
-            if app.selected > 0 {
-                app.selected -= 1;
+        KeyCode::Up => app.move_selection(true),
+        KeyCode::Down => app.move_selection(false),
+        KeyCode::Char('t') => {
+            app.tree_mode = !app.tree_mode;
+            if app.tree_mode {
+                app.sync_tree_cursor();
+                app.status = "Tree view: Left/Right collapse/expand, Space toggles".to_string();
+            } else {
+                app.status = "Flat list view".to_string();
             }
         }
-        KeyCode::Down => {
-            if app.selected + 1 < app.profiles.len() {
-                app.selected += 1;
-            }
+        KeyCode::Char('/') => {
+            app.filter_editing = true;
+            app.status = "Search: type to filter by name, path, tags or metadata".to_string();
+        }
+        KeyCode::Esc if !app.filter.is_empty() => {
+            app.filter.clear();
+            app.status = "Search cleared".to_string();
         }
         KeyCode::Char('r') => {
             app.profiles = discover_profiles()?;
             if app.selected >= app.profiles.len() {
                 app.selected = 0;
             }
+            app.snap_selection_to_filter();
             app.current_images_path = current_images_path().ok();
+            app.recheck_consistency();
+            app.details = None;
             app.status = "Profile list refreshed from ~/waydroid-images".to_string();
         }
+        KeyCode::Char('R') if !app.visible_profiles().is_empty() => {
+            app.reset_data = true;
+            app.reset_overlays = true;
+            app.screen = Screen::Reset;
+        }
+        KeyCode::Char('s') if !app.visible_profiles().is_empty() => {
+            app.snapshot_selected = 0;
+            app.snapshot_naming = false;
+            app.snapshot_pending = None;
+            app.reload_snapshots();
+            app.screen = Screen::Snapshots;
+        }
+        KeyCode::Char('P') => {
+            app.prop_selected = 0;
+            app.prop_edit = None;
+            app.prop_removing = false;
+            app.reload_props();
+            app.screen = Screen::Props;
+        }
+        KeyCode::Char('g') => {
+            app.orphan_selected = 0;
+            app.gc_target = app.selected;
+            app.gc_pending = None;
+            app.reload_orphans();
+            app.screen = Screen::Gc;
+        }
+        KeyCode::Char('u') => {
+            app.usage_selected = 0;
+            app.reload_usage();
+            app.screen = Screen::Usage;
+        }
+        KeyCode::Char('h') => {
+            match history::load() {
+                Ok(mut records) => {
+                    records.reverse();
+                    app.history = records;
+                    app.history_selected = 0;
+                    app.screen = Screen::History;
+                    app.status = format!("{} switches recorded", app.history.len());
+                }
+                Err(e) => {
+                    app.status = format!("Failed to load history: {}", e);
+                }
+            }
+        }
+        KeyCode::Char('c') if app.consistency.is_some() => {
+            app.reconcile_selected = 0;
+            app.screen = Screen::Reconcile;
+            app.status = "Pick the profile the live state should be reconciled to".to_string();
+        }
+        KeyCode::Char('d') if !app.visible_profiles().is_empty() => {
+            match DeletePlan::new(&app.profiles[app.selected]) {
+                Ok(plan) => {
+                    app.delete_plan = Some(plan);
+                    app.delete_with_store = false;
+                    app.screen = Screen::Delete;
+                }
+                Err(e) => {
+                    app.status = format!("Cannot delete: {}", e);
+                }
+            }
+        }
+        KeyCode::Char('m') if !app.visible_profiles().is_empty() => {
+            app.rename = Field::with_value("New name", app.profiles[app.selected].name.clone());
+            app.screen = Screen::Rename;
+        }
+        KeyCode::Char('C') if !app.visible_profiles().is_empty() => {
+            let name = format!("{}-copy", app.profiles[app.selected].name);
+            app.clone_name = Field::with_value("New name", name);
+            app.clone_copy_images = false;
+            app.screen = Screen::Clone;
+        }
         KeyCode::Char('a') => {
             app.manual = ManualAddState::new();
             app.screen = Screen::ManualAdd;
             app.status = "Manual add mode: enter profile name and image paths".to_string();
         }
                 KeyCode::Enter => {
-                    let selected = app.profiles[app.selected].clone();
-                    app.status = format!("Switching to '{}'...", selected.name);
-                    terminal.draw(|f| draw(f, app))?;
-
-                    match switch_to_profile(&selected.path) {
-                        Ok(logs) => {
-                            app.current_images_path = Some(selected.path.to_string_lossy().to_string());
-                            app.status = format!(
-                                "Switched to '{}'.\n{}",
-                                selected.name,
-                                logs.join("\n")
-                            );
-                        }
-                        Err(e) => {
-                            app.status = format!("Switch failed: {}", e);
-                        }
+                    if app.visible_profiles().is_empty() {
+                        return Ok(());
                     }
+                    let selected = app.profiles[app.selected].clone();
+                    request_switch(app, terminal, selected)?;
         }
+        KeyCode::Char('p') => match SwitchState::load().previous {
+            Some(previous) => {
+                let path = PathBuf::from(&previous);
+                let target = match app.profiles.iter().position(|p| p.path == path) {
+                    Some(idx) => {
+                        app.selected = idx;
+                        app.profiles[idx].clone()
+                    }
+                    None => ImageProfile {
+                        name: previous,
+                        meta: ProfileMeta::load(&path),
+                        path,
+                    },
+                };
+                request_switch(app, terminal, target)?;
+            }
+            None => {
+                app.status = "No previous profile recorded yet".to_string();
+            }
+        },
         _ => {}
     }
     Ok(())
 }
 
-fn handle_manual_key(app: &mut App, key: KeyEvent) -> Result<()> {
+/// Keys that behave differently in the tree view. Returns false for keys the
+/// flat list handler should process as usual.
+fn handle_tree_key(
+    app: &mut App,
+    key: KeyEvent,
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+) -> Result<bool> {
+    let rows = app.tree_rows();
+    if rows.is_empty() {
+        return Ok(false);
+    }
+    let cursor = app.tree_cursor.min(rows.len() - 1);
+    let row = rows[cursor].clone();
+
     match key.code {
-        KeyCode::Esc => {
-            app.screen = Screen::Profiles;
-            app.status = "Cancelled manual add".to_string();
+        KeyCode::Up => app.set_tree_cursor(&rows, cursor.saturating_sub(1)),
+        KeyCode::Down => app.set_tree_cursor(&rows, (cursor + 1).min(rows.len() - 1)),
+        KeyCode::Right => {
+            if let Some(folder) = row.folder.as_deref().filter(|_| !row.expanded) {
+                app.set_folder_expanded(folder, true);
+            }
         }
-        KeyCode::Tab | KeyCode::Down => app.manual.next(),
-        KeyCode::BackTab | KeyCode::Up => app.manual.prev(),
-        KeyCode::Enter => {
-            if app.manual.selected < 2 {
-                app.manual.next();
-            } else if app.manual.selected == 2 || app.manual.selected == 3 {
-                match save_manual_profile(app) {
-                    Ok(_) => {
-                        app.screen = Screen::Profiles;
-                    }
-                    Err(e) => {
-                        app.status = format!("Manual add failed: {}", e);
-                    }
+        KeyCode::Left => match row.folder.as_deref().filter(|_| row.expanded) {
+            Some(folder) => app.set_folder_expanded(folder, false),
+            None => {
+                if let Some(parent) = tree::parent_row(&rows, cursor) {
+                    app.set_tree_cursor(&rows, parent);
                 }
-            } else {
-                app.screen = Screen::Profiles;
-                app.status = "Cancelled manual add".to_string();
             }
-        }
-        KeyCode::Char(c) => {
-            if key.modifiers.contains(KeyModifiers::CONTROL) {
-                return Ok(());
+        },
+        KeyCode::Char(' ') => {
+            if let Some(folder) = row.folder.as_deref() {
+                app.set_folder_expanded(folder, !row.expanded);
             }
-            if let Some(field) = app.manual.selected_field_mut() {
-                field.insert_char(c);
+        }
+        KeyCode::Enter => match (row.profile, row.folder.as_deref()) {
+            (Some(idx), _) => {
+                app.selected = idx;
+                let selected = app.profiles[idx].clone();
+                request_switch(app, terminal, selected)?;
             }
+            (None, Some(folder)) => app.set_folder_expanded(folder, !row.expanded),
+            (None, None) => {}
+        },
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+fn handle_search_key(app: &mut App, key: KeyEvent) {
+    match key.code {
+        KeyCode::Esc => {
+            app.filter.clear();
+            app.filter_editing = false;
+            app.status = "Search cleared".to_string();
+        }
+        KeyCode::Enter => {
+            app.filter_editing = false;
+            app.status = format!(
+                "Filter '{}' kept: Enter switches, Esc clears",
+                app.filter
+            );
+        }
+        KeyCode::Up if !app.tree_mode => app.move_selection(true),
+        KeyCode::Down if !app.tree_mode => app.move_selection(false),
+        KeyCode::Up | KeyCode::Down => {
+            let rows = app.tree_rows();
+            let cursor = if key.code == KeyCode::Up {
+                app.tree_cursor.saturating_sub(1)
+            } else {
+                (app.tree_cursor + 1).min(rows.len().saturating_sub(1))
+            };
+            app.set_tree_cursor(&rows, cursor);
         }
         KeyCode::Backspace => {
-            if let Some(field) = app.manual.selected_field_mut() {
-                field.backspace();
-            }
+            app.filter.pop();
+            app.snap_selection_to_filter();
         }
-        KeyCode::Left => {
-            if let Some(field) = app.manual.selected_field_mut() {
-                field.move_left();
-            }
+        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+            app.filter.push(c);
+            app.snap_selection_to_filter();
         }
-        KeyCode::Right => {
-            if let Some(field) = app.manual.selected_field_mut() {
-                field.move_right();
+        _ => {}
+    }
+}
+
+/// Switches straight away when confirmation is disabled in the config,
+/// otherwise opens the confirmation modal.
+fn request_switch(
+    app: &mut App,
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    target: ImageProfile,
+) -> Result<()> {
+    if !app.config.confirm_switch {
+        app.screen = Screen::Profiles;
+        return switch_from_tui(app, terminal, target);
+    }
+    app.pending = Some(PendingSwitch {
+        target,
+        session_running: session_running(),
+    });
+    app.screen = Screen::Confirm;
+    Ok(())
+}
+
+fn handle_confirm_key(
+    app: &mut App,
+    key: KeyEvent,
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+) -> Result<()> {
+    match key.code {
+        KeyCode::Enter | KeyCode::Char('y') => {
+            app.screen = Screen::Profiles;
+            if let Some(pending) = app.pending.take() {
+                switch_from_tui(app, terminal, pending.target)?;
             }
         }
+        KeyCode::Esc | KeyCode::Char('n') => {
+            app.screen = Screen::Profiles;
+            app.pending = None;
+            app.status = "Switch cancelled".to_string();
+        }
         _ => {}
     }
     Ok(())
 }
 
-fn save_manual_profile(app: &mut App) -> Result<()> {
-    let name = app.manual.fields[0].value.trim();
-    let system = app.manual.fields[1].value.trim();
-    let vendor = app.manual.fields[2].value.trim();
-
-    if name.is_empty() || system.is_empty() || vendor.is_empty() {
-        bail!("All fields are required");
+fn handle_delete_key(app: &mut App, key: KeyEvent) -> Result<()> {
+    match key.code {
+        KeyCode::Char('s') => app.delete_with_store = !app.delete_with_store,
+        KeyCode::Enter | KeyCode::Char('y') => {
+            app.screen = Screen::Profiles;
+            let Some(plan) = app.delete_plan.take() else {
+                return Ok(());
+            };
+            match plan.execute(app.delete_with_store) {
+                Ok(logs) => {
+                    app.profiles = discover_profiles()?;
+                    app.selected = app.selected.min(app.profiles.len().saturating_sub(1));
+                    app.snap_selection_to_filter();
+                    app.details = None;
+                    app.recheck_consistency();
+                    app.status = format!("Deleted '{}'.\n{}", plan.profile.name, logs.join("\n"));
+                }
+                Err(e) => {
+                    app.status = format!("Delete failed: {}", e);
+                }
+            }
+        }
+        KeyCode::Esc | KeyCode::Char('n') => {
+            app.screen = Screen::Profiles;
+            app.delete_plan = None;
+            app.status = "Delete cancelled".to_string();
+        }
+        _ => {}
     }
+    Ok(())
+}
 
-    let system_path = PathBuf::from(system);
-    let vendor_path = PathBuf::from(vendor);
-
-    if !system_path.is_file() {
-        bail!("System image not found: {}", system_path.display());
-    }
-    if !vendor_path.is_file() {
+fn handle_reset_key(app: &mut App, key: KeyEvent) {
+    match key.code {
+        KeyCode::Char('u') => app.reset_data = !app.reset_data,
+        KeyCode::Char('o') => app.reset_overlays = !app.reset_overlays,
+        KeyCode::Char('y') => {
+            app.screen = Screen::Profiles;
+            let profile = app.profiles[app.selected].clone();
+            match profile_ops::reset_profile(&profile, app.reset_data, app.reset_overlays) {
+                Ok(logs) => {
+                    app.details = None;
+                    app.status = format!("Reset '{}'.\n{}", profile.name, logs.join("\n"));
+                }
+                Err(e) => {
+                    app.status = format!("Reset failed: {}", e);
+                }
+            }
+        }
+        KeyCode::Esc | KeyCode::Char('n') => {
+            app.screen = Screen::Profiles;
+            app.status = "Reset cancelled".to_string();
+        }
+        _ => {}
+    }
+}
+
+fn handle_rename_key(app: &mut App, key: KeyEvent) -> Result<()> {
+    match key.code {
+        KeyCode::Esc => {
+            app.screen = Screen::Profiles;
+            app.status = "Rename cancelled".to_string();
+        }
+        KeyCode::Enter => {
+            app.screen = Screen::Profiles;
+            let profile = app.profiles[app.selected].clone();
+            match profile_ops::rename_profile(&profile, &app.rename.value) {
+                Ok((new_path, logs)) => {
+                    app.profiles = discover_profiles()?;
+                    if let Some(idx) = app.profiles.iter().position(|p| p.path == new_path) {
+                        app.selected = idx;
+                    }
+                    app.snap_selection_to_filter();
+                    app.current_images_path = current_images_path().ok();
+                    app.details = None;
+                    app.recheck_consistency();
+                    app.status = format!("Renamed '{}'.\n{}", profile.name, logs.join("\n"));
+                }
+                Err(e) => {
+                    app.status = format!("Rename failed: {}", e);
+                }
+            }
+        }
+        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+            app.rename.insert_char(c);
+        }
+        KeyCode::Backspace => app.rename.backspace(),
+        KeyCode::Left => app.rename.move_left(),
+        KeyCode::Right => app.rename.move_right(),
+        _ => {}
+    }
+    Ok(())
+}
+
+fn handle_clone_key(app: &mut App, key: KeyEvent) -> Result<()> {
+    match key.code {
+        KeyCode::Esc => {
+            app.screen = Screen::Profiles;
+            app.status = "Clone cancelled".to_string();
+        }
+        KeyCode::Tab => app.clone_copy_images = !app.clone_copy_images,
+        KeyCode::Enter => {
+            app.screen = Screen::Profiles;
+            let profile = app.profiles[app.selected].clone();
+            let copy_images = app.clone_copy_images;
+            match profile_ops::clone_profile(&profile, &app.clone_name.value, copy_images) {
+                Ok((new_path, logs)) => {
+                    app.profiles = discover_profiles()?;
+                    if let Some(idx) = app.profiles.iter().position(|p| p.path == new_path) {
+                        app.selected = idx;
+                    }
+                    app.snap_selection_to_filter();
+                    app.details = None;
+                    app.status = format!("Cloned '{}'.\n{}", profile.name, logs.join("\n"));
+                }
+                Err(e) => {
+                    app.status = format!("Clone failed: {}", e);
+                }
+            }
+        }
+        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+            app.clone_name.insert_char(c);
+        }
+        KeyCode::Backspace => app.clone_name.backspace(),
+        KeyCode::Left => app.clone_name.move_left(),
+        KeyCode::Right => app.clone_name.move_right(),
+        _ => {}
+    }
+    Ok(())
+}
+
+fn switch_from_tui(
+    app: &mut App,
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    target: ImageProfile,
+) -> Result<()> {
+    app.status = format!("Switching to '{}'...", target.name);
+    terminal.draw(|f| draw(f, app))?;
+
+    match switch_to_profile(&target.path) {
+        Ok(logs) => {
+            app.current_images_path = Some(target.path.to_string_lossy().to_string());
+            app.recheck_consistency();
+            app.details = None;
+            app.status = format!("Switched to '{}'.\n{}", target.name, logs.join("\n"));
+        }
+        Err(e) => {
+            app.status = format!("Switch failed: {}", e);
+        }
+    }
+    Ok(())
+}
+
+fn handle_reconcile_key(
+    app: &mut App,
+    key: KeyEvent,
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+) -> Result<()> {
+    let candidates = app
+        .consistency
+        .as_ref()
+        .map(|c| c.candidates(&app.profiles))
+        .unwrap_or_default();
+
+    match key.code {
+        KeyCode::Esc => {
+            app.screen = Screen::Profiles;
+            app.status = "Reconcile cancelled".to_string();
+        }
+        KeyCode::Up if app.reconcile_selected > 0 => {
+            app.reconcile_selected -= 1;
+        }
+        KeyCode::Down if app.reconcile_selected + 1 < candidates.len() => {
+            app.reconcile_selected += 1;
+        }
+        KeyCode::Enter => {
+            let Some(&(idx, _)) = candidates.get(app.reconcile_selected) else {
+                return Ok(());
+            };
+            let target = app.profiles[idx].clone();
+            app.selected = idx;
+            app.screen = Screen::Profiles;
+            request_switch(app, terminal, target)?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn handle_history_key(app: &mut App, key: KeyEvent) {
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q') => {
+            app.screen = Screen::Profiles;
+            app.status = "Back to profiles".to_string();
+        }
+        KeyCode::Up if app.history_selected > 0 => {
+            app.history_selected -= 1;
+        }
+        KeyCode::Down if app.history_selected + 1 < app.history.len() => {
+            app.history_selected += 1;
+        }
+        _ => {}
+    }
+}
+
+fn handle_snapshots_key(app: &mut App, key: KeyEvent) -> Result<()> {
+    let profile = app.profiles[app.selected].clone();
+
+    if app.snapshot_naming {
+        match key.code {
+            KeyCode::Esc => {
+                app.snapshot_naming = false;
+                app.status = "Snapshot cancelled".to_string();
+            }
+            KeyCode::Enter => {
+                app.snapshot_naming = false;
+                match snapshot::create(&profile, &app.snapshot_name.value) {
+                    Ok((created, logs)) => {
+                        app.reload_snapshots();
+                        app.status = format!("Saved '{}'.\n{}", created.id(), logs.join("\n"));
+                    }
+                    Err(e) => app.status = format!("Snapshot failed: {}", e),
+                }
+                app.current_images_path = current_images_path().ok();
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                app.snapshot_name.insert_char(c);
+            }
+            KeyCode::Backspace => app.snapshot_name.backspace(),
+            KeyCode::Left => app.snapshot_name.move_left(),
+            KeyCode::Right => app.snapshot_name.move_right(),
+            _ => {}
+        }
+        return Ok(());
+    }
+
+    if let Some(action) = app.snapshot_pending.take() {
+        let Some(chosen) = app.snapshots.get(app.snapshot_selected).cloned() else {
+            return Ok(());
+        };
+        if key.code != KeyCode::Char('y') {
+            app.status = "Cancelled".to_string();
+            return Ok(());
+        }
+        let result = match action {
+            SnapshotAction::Restore => snapshot::restore(&profile, &chosen),
+            SnapshotAction::Delete => snapshot::remove(&chosen),
+        };
+        match result {
+            Ok(logs) => app.status = logs.join("\n"),
+            Err(e) => app.status = format!("Snapshot {} failed: {}", chosen.id(), e),
+        }
+        app.reload_snapshots();
+        app.details = None;
+        return Ok(());
+    }
+
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q') => {
+            app.screen = Screen::Profiles;
+            app.status = "Back to profiles".to_string();
+        }
+        KeyCode::Up if app.snapshot_selected > 0 => {
+            app.snapshot_selected -= 1;
+        }
+        KeyCode::Down if app.snapshot_selected + 1 < app.snapshots.len() => {
+            app.snapshot_selected += 1;
+        }
+        KeyCode::Char('n') => {
+            app.snapshot_name = Field::new("Snapshot name");
+            app.snapshot_naming = true;
+        }
+        KeyCode::Enter if !app.snapshots.is_empty() => {
+            let active =
+                app.current_images_path.as_deref() == Some(profile.path.to_string_lossy().as_ref());
+            app.snapshot_pending = Some(SnapshotAction::Restore);
+            app.status = format!(
+                "Restore '{}'? The userdata and overlays of '{}' are replaced{}.\ny: confirm, any other key: cancel",
+                app.snapshots[app.snapshot_selected].id(),
+                profile.name,
+                if active { " and the session is stopped" } else { "" }
+            );
+        }
+        KeyCode::Char('x') if !app.snapshots.is_empty() => {
+            app.snapshot_pending = Some(SnapshotAction::Delete);
+            app.status = format!(
+                "Delete snapshot '{}'? y: confirm, any other key: cancel",
+                app.snapshots[app.snapshot_selected].id()
+            );
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn handle_props_key(app: &mut App, key: KeyEvent) {
+    if let Some(edit) = app.prop_edit.clone() {
+        match key.code {
+            KeyCode::Esc => {
+                app.prop_edit = None;
+                app.status = "Edit cancelled".to_string();
+            }
+            KeyCode::Tab if matches!(edit, PropEdit::NewKey) => {
+                app.prop_new_source = match app.prop_new_source {
+                    PropSource::Cfg => PropSource::Persist,
+                    PropSource::Persist => PropSource::Cfg,
+                };
+            }
+            KeyCode::Enter => match edit {
+                PropEdit::NewKey => {
+                    let key = app.prop_field.value.trim().to_string();
+                    if key.is_empty() || key.contains(char::is_whitespace) || key.contains('=') {
+                        app.status = "Property keys cannot be empty or contain spaces or '='"
+                            .to_string();
+                        return;
+                    }
+                    app.prop_edit = Some(PropEdit::Value {
+                        key,
+                        source: app.prop_new_source,
+                    });
+                    app.prop_field = Field::new("Value");
+                }
+                PropEdit::Value { key, source } => {
+                    app.prop_edit = None;
+                    let value = app.prop_field.value.trim().to_string();
+                    match props::apply(source, &key, Some(&value)) {
+                        Ok(logs) => app.status = logs.join("\n"),
+                        Err(e) => app.status = format!("Setting {} failed: {}", key, e),
+                    }
+                    app.reload_props();
+                }
+            },
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                app.prop_field.insert_char(c);
+            }
+            KeyCode::Backspace => app.prop_field.backspace(),
+            KeyCode::Left => app.prop_field.move_left(),
+            KeyCode::Right => app.prop_field.move_right(),
+            _ => {}
+        }
+        return;
+    }
+
+    if app.prop_removing {
+        app.prop_removing = false;
+        let Some(prop) = app.props.get(app.prop_selected).cloned() else {
+            return;
+        };
+        if key.code != KeyCode::Char('y') {
+            app.status = "Cancelled".to_string();
+            return;
+        }
+        match props::apply(prop.source, &prop.key, None) {
+            Ok(logs) => app.status = logs.join("\n"),
+            Err(e) => app.status = format!("Removing {} failed: {}", prop.key, e),
+        }
+        app.reload_props();
+        return;
+    }
+
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q') => {
+            app.screen = Screen::Profiles;
+            app.status = "Back to profiles".to_string();
+        }
+        KeyCode::Up if app.prop_selected > 0 => {
+            app.prop_selected -= 1;
+        }
+        KeyCode::Down if app.prop_selected + 1 < app.props.len() => {
+            app.prop_selected += 1;
+        }
+        KeyCode::Char('r') => {
+            app.reload_props();
+            app.status = "Properties reloaded".to_string();
+        }
+        KeyCode::Char('n') => {
+            app.prop_field = Field::new("Key");
+            app.prop_new_source = PropSource::Cfg;
+            app.prop_edit = Some(PropEdit::NewKey);
+        }
+        KeyCode::Enter | KeyCode::Char('e') if !app.props.is_empty() => {
+            let prop = &app.props[app.prop_selected];
+            app.prop_field = Field::with_value("Value", prop.value.clone().unwrap_or_default());
+            app.prop_edit = Some(PropEdit::Value {
+                key: prop.key.clone(),
+                source: prop.source,
+            });
+        }
+        KeyCode::Char('x') if !app.props.is_empty() => {
+            let prop = &app.props[app.prop_selected];
+            app.prop_removing = true;
+            app.status = match prop.source {
+                PropSource::Cfg => format!(
+                    "Remove {} from [properties] and run 'waydroid upgrade --offline'? y: confirm, any other key: cancel",
+                    prop.key
+                ),
+                PropSource::Persist => format!(
+                    "Clear {} in the running session? y: confirm, any other key: cancel",
+                    prop.key
+                ),
+            };
+        }
+        _ => {}
+    }
+}
+
+fn handle_gc_key(app: &mut App, key: KeyEvent) -> Result<()> {
+    let Some(target) = app.profiles.get(app.gc_target).cloned() else {
+        app.screen = Screen::Profiles;
+        return Ok(());
+    };
+
+    if let Some(action) = app.gc_pending.take() {
+        let Some(orphan) = app.orphans.get(app.orphan_selected).cloned() else {
+            return Ok(());
+        };
+        if key.code != KeyCode::Char('y') {
+            app.status = "Cancelled".to_string();
+            return Ok(());
+        }
+        let result = match action {
+            GcAction::Delete => gc::remove(&orphan),
+            GcAction::Attach(mode) => gc::attach(&orphan, &target, mode),
+        };
+        app.reload_orphans();
+        match result {
+            Ok(logs) => app.status = logs.join("\n"),
+            Err(e) => app.status = format!("{} failed: {}", orphan.id, e),
+        }
+        app.details = None;
+        return Ok(());
+    }
+
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q') => {
+            app.screen = Screen::Profiles;
+            app.status = "Back to profiles".to_string();
+        }
+        KeyCode::Up if app.orphan_selected > 0 => {
+            app.orphan_selected -= 1;
+        }
+        KeyCode::Down if app.orphan_selected + 1 < app.orphans.len() => {
+            app.orphan_selected += 1;
+        }
+        KeyCode::Left => {
+            app.gc_target = app
+                .gc_target
+                .checked_sub(1)
+                .unwrap_or(app.profiles.len() - 1);
+        }
+        KeyCode::Right => app.gc_target = (app.gc_target + 1) % app.profiles.len(),
+        KeyCode::Char('r') => app.reload_orphans(),
+        KeyCode::Char('x') if !app.orphans.is_empty() => {
+            app.gc_pending = Some(GcAction::Delete);
+            app.status = format!(
+                "Delete {}? y: confirm, any other key: cancel",
+                app.orphans[app.orphan_selected].path.display()
+            );
+        }
+        KeyCode::Char(c @ ('a' | 'w' | 'm')) if !app.orphans.is_empty() => {
+            let (mode, verb) = match c {
+                'w' => (AttachMode::Swap, "Swap"),
+                'm' => (AttachMode::Merge, "Merge"),
+                _ => (AttachMode::Move, "Attach"),
+            };
+            app.gc_pending = Some(GcAction::Attach(mode));
+            app.status = format!(
+                "{} '{}' as the userdata/overlays of '{}'? y: confirm, any other key: cancel",
+                verb, app.orphans[app.orphan_selected].id, target.name
+            );
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn handle_usage_key(app: &mut App, key: KeyEvent) {
+    let rows = app.usage.as_ref().map_or(0, |u| u.profiles.len());
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q') => {
+            app.screen = Screen::Profiles;
+            app.status = "Back to profiles".to_string();
+        }
+        KeyCode::Up if app.usage_selected > 0 => {
+            app.usage_selected -= 1;
+        }
+        KeyCode::Down if app.usage_selected + 1 < rows => {
+            app.usage_selected += 1;
+        }
+        KeyCode::Char('r') => app.reload_usage(),
+        KeyCode::Enter => {
+            let name = app
+                .usage
+                .as_ref()
+                .and_then(|u| u.profiles.get(app.usage_selected))
+                .map(|row| row.name.clone());
+            if let Some(idx) = name.and_then(|n| app.profiles.iter().position(|p| p.name == n)) {
+                app.selected = idx;
+                app.snap_selection_to_filter();
+            }
+            app.screen = Screen::Profiles;
+        }
+        _ => {}
+    }
+}
+
+fn handle_manual_key(app: &mut App, key: KeyEvent) -> Result<()> {
+    match key.code {
+        KeyCode::Esc => {
+            app.screen = Screen::Profiles;
+            app.status = "Cancelled manual add".to_string();
+        }
+        KeyCode::Tab | KeyCode::Down => app.manual.next(),
+        KeyCode::BackTab | KeyCode::Up => app.manual.prev(),
+        KeyCode::Enter => {
+            if app.manual.selected < 2 {
+                app.manual.next();
+            } else if app.manual.selected == 2 || app.manual.selected == 3 {
+                match save_manual_profile(app) {
+                    Ok(_) => {
+                        app.screen = Screen::Profiles;
+                    }
+                    Err(e) => {
+                        app.status = format!("Manual add failed: {}", e);
+                    }
+                }
+            } else {
+                app.screen = Screen::Profiles;
+                app.status = "Cancelled manual add".to_string();
+            }
+        }
+        KeyCode::Char(c) => {
+            if key.modifiers.contains(KeyModifiers::CONTROL) {
+                return Ok(());
+            }
+            if let Some(field) = app.manual.selected_field_mut() {
+                field.insert_char(c);
+            }
+        }
+        KeyCode::Backspace => {
+            if let Some(field) = app.manual.selected_field_mut() {
+                field.backspace();
+            }
+        }
+        KeyCode::Left => {
+            if let Some(field) = app.manual.selected_field_mut() {
+                field.move_left();
+            }
+        }
+        KeyCode::Right => {
+            if let Some(field) = app.manual.selected_field_mut() {
+                field.move_right();
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn save_manual_profile(app: &mut App) -> Result<()> {
+    let name = app.manual.fields[0].value.trim();
+    let system = app.manual.fields[1].value.trim();
+    let vendor = app.manual.fields[2].value.trim();
+
+    if name.is_empty() || system.is_empty() || vendor.is_empty() {
+        bail!("All fields are required");
+    }
+
+    let system_path = PathBuf::from(system);
+    let vendor_path = PathBuf::from(vendor);
+
+    if !system_path.is_file() {
+        bail!("System image not found: {}", system_path.display());
+    }
+    if !vendor_path.is_file() {
         bail!("Vendor image not found: {}", vendor_path.display());
     }
 
+    let _lock = lock::acquire("import")?;
     let base = home_dir()
         .context("Failed to resolve HOME")?
         .join("waydroid-images");
     fs::create_dir_all(&base)?;
 
-    let safe_name = name.replace('/', "-").replace('\\', "-");
+    let safe_name = name.replace(['/', '\\'], "-");
     let profile_dir = base.join(&safe_name);
     fs::create_dir_all(&profile_dir)?;
 
@@ -359,7 +1643,11 @@ fn discover_profiles() -> Result<Vec<ImageProfile>> {
 
     let profiles = map
         .into_iter()
-        .map(|(name, path)| ImageProfile { name, path })
+        .map(|(name, path)| ImageProfile {
+            name,
+            meta: ProfileMeta::load(&path),
+            path,
+        })
         .collect::<Vec<_>>();
 
     Ok(profiles)
@@ -405,29 +1693,67 @@ fn current_images_path() -> Result<String> {
 }
 
 fn switch_to_profile(path: &Path) -> Result<Vec<String>> {
+    let _lock = lock::acquire("switch")?;
+    let from = current_images_path().ok();
     let mut logs = Vec::new();
+    let result = run_switch_steps(path, &mut logs);
+
+    let outcome = match &result {
+        Ok(()) => "ok".to_string(),
+        Err(err) => format!("failed: {}", err),
+    };
+    let record = SwitchRecord::new(from, path.display().to_string(), outcome, logs.clone());
+    if let Err(err) = history::append(&record) {
+        logs.push(format!("history warning: {}", err));
+    }
+    if result.is_ok() {
+        let mut state = SwitchState::load();
+        state.record_switch(&record.to, record.from.as_deref());
+        if let Err(err) = state.save() {
+            logs.push(format!("state warning: {}", err));
+        }
+    }
 
+    result.map(|()| logs)
+}
+
+fn run_switch_steps(path: &Path, logs: &mut Vec<String>) -> Result<()> {
     if !path.join("system.img").is_file() || !path.join("vendor.img").is_file() {
         bail!("{} missing system.img/vendor.img", path.display());
     }
     logs.push(format!("Selected path: {}", path.display()));
 
-    match run_cmd("sudo", &["waydroid", "session", "stop"]) {
-        Ok(msg) => logs.push(format!("session stop: {}", msg)),
-        Err(err) => logs.push(format!("session stop warning: {}", err)),
-    }
-    match run_cmd("sudo", &["waydroid", "container", "stop"]) {
-        Ok(msg) => logs.push(format!("container stop: {}", msg)),
-        Err(err) => logs.push(format!("container stop warning: {}", err)),
-    }
+    stop_session(logs);
 
-    setup_profile_userdata(path, &mut logs)?;
-    maybe_migrate_global_overlay(&mut logs)?;
-    setup_profile_overlays(path, &mut logs)?;
+    let config = Config::load();
+    let outgoing = current_images_path().ok().map(PathBuf::from);
+    if config.snapshot_before_switch {
+        if let Some(outgoing) = outgoing.as_deref().filter(|o| *o != path) {
+            snapshot::pre_switch(outgoing, config.snapshot_keep, logs)?;
+        }
+    }
 
-    let sed_expr = format!("s#^images_path = .*#images_path = {}#", path.display());
-    let sed_msg = run_cmd("sudo", &["sed", "-i", &sed_expr, WAYDROID_CFG])?;
-    logs.push(format!("config update: {}", sed_msg));
+    setup_profile_userdata(path, logs)?;
+    maybe_migrate_global_overlay(logs)?;
+    setup_profile_overlays(path, logs)?;
+
+    set_images_path(path, logs)?;
+    build_date::sync(path, logs)?;
+    let props_changed = overrides::apply(path, logs)?;
+
+    // `[properties]` only reach the container through a regenerated
+    // waydroid_base.prop, so an override change always needs the upgrade.
+    let upgrade = props_changed
+        || match config.upgrade {
+            UpgradeMode::Never => false,
+            UpgradeMode::Always => true,
+            UpgradeMode::Auto => vendor_changed(outgoing.as_deref(), path),
+        };
+    if upgrade {
+        if let Err(err) = upgrade_offline(logs) {
+            logs.push(format!("upgrade warning: {}", err));
+        }
+    }
 
     if std::env::var("DBUS_SESSION_BUS_ADDRESS").is_err() {
         if let Ok(xdg) = std::env::var("XDG_RUNTIME_DIR") {
@@ -445,14 +1771,92 @@ fn switch_to_profile(path: &Path) -> Result<Vec<String>> {
         .context("Failed to spawn waydroid session start")?;
     logs.push(format!("session start: spawned pid {}", child.id()));
 
-    Ok(logs)
+    Ok(())
+}
+
+fn set_images_path(path: &Path, logs: &mut Vec<String>) -> Result<()> {
+    let sed_expr = format!("s#^images_path = .*#images_path = {}#", path.display());
+    let sed_msg = run_cmd("sudo", &["sed", "-i", &sed_expr, WAYDROID_CFG])?;
+    logs.push(format!("config update: {}", sed_msg));
+    Ok(())
+}
+
+/// Rewrites waydroid.cfg in place through `sudo tee`, keeping its owner and
+/// mode. Nothing is staged in a file another user could swap out.
+fn write_waydroid_cfg(ini: &Ini) -> Result<()> {
+    use std::io::Write;
+
+    let mut child = Command::new("sudo")
+        .args(["tee", WAYDROID_CFG])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to run: sudo tee")?;
+    child
+        .stdin
+        .take()
+        .context("sudo tee has no stdin")?
+        .write_all(ini.render().as_bytes())
+        .with_context(|| format!("Failed writing {}", WAYDROID_CFG))?;
+    let out = child.wait_with_output()?;
+    if !out.status.success() {
+        bail!(
+            "Failed writing {}: {}",
+            WAYDROID_CFG,
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Regenerates `waydroid_base.prop` and the LXC config from waydroid.cfg.
+fn upgrade_offline(logs: &mut Vec<String>) -> Result<()> {
+    let msg = run_cmd("sudo", &["waydroid", "upgrade", "--offline"])?;
+    logs.push(format!("upgrade: {}", msg));
+    Ok(())
+}
+
+/// Whether switching from `outgoing` to `path` brings a different vendor
+/// image, comparing resolved files. An unknown outgoing profile counts.
+fn vendor_changed(outgoing: Option<&Path>, path: &Path) -> bool {
+    let vendor = |dir: &Path| fs::canonicalize(dir.join("vendor.img")).ok();
+    match outgoing.and_then(vendor) {
+        Some(old) => Some(old) != vendor(path),
+        None => true,
+    }
+}
+
+/// Log line for operations that stopped the active profile's session.
+fn session_left_stopped(area: &str) -> String {
+    format!("{}: session left stopped, switch or start it again", area)
+}
+
+/// Whether `waydroid status` reports a running session, `None` if unknown.
+fn session_running() -> Option<bool> {
+    let status = run_cmd("waydroid", &["status"]).ok()?;
+    status
+        .lines()
+        .find(|l| l.trim_start().starts_with("Session:"))
+        .map(|l| l.contains("RUNNING"))
+}
+
+fn stop_session(logs: &mut Vec<String>) {
+    match run_cmd("sudo", &["waydroid", "session", "stop"]) {
+        Ok(msg) => logs.push(format!("session stop: {}", msg)),
+        Err(err) => logs.push(format!("session stop warning: {}", err)),
+    }
+    match run_cmd("sudo", &["waydroid", "container", "stop"]) {
+        Ok(msg) => logs.push(format!("container stop: {}", msg)),
+        Err(err) => logs.push(format!("container stop warning: {}", err)),
+    }
 }
 
 fn setup_profile_userdata(path: &Path, logs: &mut Vec<String>) -> Result<()> {
     let home = home_dir().context("Failed to resolve HOME")?;
     let waydroid_state = home.join(".local/share/waydroid");
     let live_data = waydroid_state.join("data");
-    let profiles_root = waydroid_state.join("profiles");
+    let profiles_root = profiles_root(&home);
     let profile_id = profile_id_from_path(path, &home);
     let profile_data = profiles_root.join(&profile_id).join("data");
 
@@ -469,7 +1873,9 @@ fn setup_profile_userdata(path: &Path, logs: &mut Vec<String>) -> Result<()> {
         }
     }
 
-    if live_data.exists() {
+    // A dangling link (the store it pointed at was moved, e.g. by a rename)
+    // does not `exists()` but still has to go before relinking.
+    if live_data.exists() || live_data.is_symlink() {
         if live_data.is_symlink() {
             fs::remove_file(&live_data)
                 .with_context(|| format!("Failed removing symlink {}", live_data.display()))?;
@@ -526,8 +1932,8 @@ fn setup_profile_userdata(path: &Path, logs: &mut Vec<String>) -> Result<()> {
 }
 
 fn maybe_migrate_global_overlay(logs: &mut Vec<String>) -> Result<()> {
-    let overlay_rw_live = Path::new("/var/lib/waydroid/overlay_rw");
-    let overlay_work_live = Path::new("/var/lib/waydroid/overlay_work");
+    let overlay_rw_live = Path::new(OVERLAY_RW_LIVE);
+    let overlay_work_live = Path::new(OVERLAY_WORK_LIVE);
 
     let rw_is_link = symlink_path_points_somewhere(overlay_rw_live);
     let work_is_link = symlink_path_points_somewhere(overlay_work_live);
@@ -540,182 +1946,984 @@ fn maybe_migrate_global_overlay(logs: &mut Vec<String>) -> Result<()> {
         return Ok(());
     };
 
-    let home = home_dir().context("Failed to resolve HOME")?;
-    let current_profile_id = profile_id_from_path(Path::new(&current), &home);
-    let profile_root = home
-        .join(".local/share/waydroid/profiles")
-        .join(current_profile_id);
-    let profile_overlay_rw = profile_root.join("overlay_rw");
-    let profile_overlay_work = profile_root.join("overlay_work");
-    let profile_overlay_rw_s = profile_overlay_rw.to_string_lossy().to_string();
-    let profile_overlay_work_s = profile_overlay_work.to_string_lossy().to_string();
+    let home = home_dir().context("Failed to resolve HOME")?;
+    let current_profile_id = profile_id_from_path(Path::new(&current), &home);
+    let profile_root = profiles_root(&home).join(current_profile_id);
+    let profile_overlay_rw = profile_root.join("overlay_rw");
+    let profile_overlay_work = profile_root.join("overlay_work");
+    let profile_overlay_rw_s = profile_overlay_rw.to_string_lossy().to_string();
+    let profile_overlay_work_s = profile_overlay_work.to_string_lossy().to_string();
+
+    fs::create_dir_all(&profile_root)?;
+
+    if overlay_rw_live.exists() && !rw_is_link && !profile_overlay_rw.exists() {
+        run_cmd("sudo", &["mv", OVERLAY_RW_LIVE, &profile_overlay_rw_s])?;
+        logs.push("overlay migration: moved legacy overlay_rw into active profile".to_string());
+    }
+    if overlay_work_live.exists() && !work_is_link && !profile_overlay_work.exists() {
+        run_cmd("sudo", &["mv", OVERLAY_WORK_LIVE, &profile_overlay_work_s])?;
+        logs.push("overlay migration: moved legacy overlay_work into active profile".to_string());
+    }
+
+    Ok(())
+}
+
+fn setup_profile_overlays(path: &Path, logs: &mut Vec<String>) -> Result<()> {
+    let home = home_dir().context("Failed to resolve HOME")?;
+    let profile_id = profile_id_from_path(path, &home);
+    let profile_root = profiles_root(&home).join(&profile_id);
+    let profile_overlay_rw = profile_root.join("overlay_rw");
+    let profile_overlay_work = profile_root.join("overlay_work");
+
+    fs::create_dir_all(profile_overlay_rw.join("system"))?;
+    fs::create_dir_all(profile_overlay_rw.join("vendor"))?;
+    fs::create_dir_all(profile_overlay_work.join("system"))?;
+    fs::create_dir_all(profile_overlay_work.join("vendor"))?;
+
+    let profile_overlay_rw_s = profile_overlay_rw.to_string_lossy().to_string();
+    let profile_overlay_work_s = profile_overlay_work.to_string_lossy().to_string();
+
+    run_cmd("sudo", &["rm", "-rf", OVERLAY_RW_LIVE])?;
+    run_cmd("sudo", &["rm", "-rf", OVERLAY_WORK_LIVE])?;
+    run_cmd("sudo", &["ln", "-s", &profile_overlay_rw_s, OVERLAY_RW_LIVE])?;
+    run_cmd("sudo", &["ln", "-s", &profile_overlay_work_s, OVERLAY_WORK_LIVE])?;
+
+    logs.push(format!(
+        "overlay: active profile '{}' -> {}",
+        profile_id,
+        profile_root.display()
+    ));
+
+    Ok(())
+}
+
+fn symlink_path_points_somewhere(path: &Path) -> bool {
+    match fs::symlink_metadata(path) {
+        Ok(meta) => meta.file_type().is_symlink(),
+        Err(_) => false,
+    }
+}
+
+/// Per-user state of the switcher itself (history, last profile).
+fn app_state_dir() -> Result<PathBuf> {
+    let base = match dirs::state_dir() {
+        Some(dir) => dir,
+        None => home_dir()
+            .context("Failed to resolve HOME")?
+            .join(".local/state"),
+    };
+    Ok(base.join("waydroid-switch"))
+}
+
+fn profiles_root(home: &Path) -> PathBuf {
+    home.join(".local/share/waydroid/profiles")
+}
+
+fn profile_id_from_path(path: &Path, home: &Path) -> String {
+    let base = home.join("waydroid-images");
+    let raw = if let Ok(rel) = path.strip_prefix(&base) {
+        rel.to_string_lossy().to_string()
+    } else {
+        path.to_string_lossy().to_string()
+    };
+
+    let mut hasher = DefaultHasher::new();
+    raw.hash(&mut hasher);
+    let hash = hasher.finish();
+
+    let mut cleaned = raw
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    while cleaned.contains("__") {
+        cleaned = cleaned.replace("__", "_");
+    }
+    cleaned = cleaned.trim_matches('_').to_string();
+    if cleaned.is_empty() {
+        cleaned = "profile".to_string();
+    }
+    format!("{}_{:08x}", cleaned, (hash & 0xffff_ffff))
+}
+
+fn run_cmd(cmd: &str, args: &[&str]) -> Result<String> {
+    let out = Command::new(cmd)
+        .args(args)
+        .output()
+        .with_context(|| format!("Failed to run: {} {}", cmd, args.join(" ")))?;
+
+    if out.status.success() {
+        let stdout = String::from_utf8_lossy(&out.stdout).trim().to_string();
+        if stdout.is_empty() {
+            return Ok("ok".to_string());
+        }
+        return Ok(stdout);
+    }
+
+    let stderr = String::from_utf8_lossy(&out.stderr).trim().to_string();
+    let stdout = String::from_utf8_lossy(&out.stdout).trim().to_string();
+    let msg = if !stderr.is_empty() {
+        stderr
+    } else if !stdout.is_empty() {
+        stdout
+    } else {
+        "command failed".to_string()
+    };
+
+    bail!("{} {} -> {}", cmd, args.join(" "), msg)
+}
+
+/// Size of a directory tree in KiB as reported by `du`, `None` if unreadable.
+fn disk_usage_kb(path: &Path) -> Option<u64> {
+    // `du` still prints a total when parts of the tree (root-owned overlay
+    // work dirs) are unreadable, so its exit status is ignored.
+    let out = Command::new("du")
+        .arg("-sk")
+        .arg(path)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&out.stdout)
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+fn human_size_kb(kb: u64) -> String {
+    if kb >= 1024 * 1024 {
+        format!("{:.1} GiB", kb as f64 / (1024.0 * 1024.0))
+    } else if kb >= 1024 {
+        format!("{:.1} MiB", kb as f64 / 1024.0)
+    } else {
+        format!("{} KiB", kb)
+    }
+}
+
+fn prompt_yes_no(question: &str) -> Result<bool> {
+    use std::io::Write;
+
+    print!("{} [y/N] ", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+fn draw(f: &mut Frame, app: &App) {
+    match app.screen {
+        Screen::Profiles => draw_profiles(f, app),
+        Screen::ManualAdd => draw_manual_add(f, app),
+        Screen::Reconcile => draw_reconcile(f, app),
+        Screen::History => draw_history(f, app),
+        Screen::Confirm => {
+            draw_profiles(f, app);
+            draw_confirm(f, app);
+        }
+        Screen::Delete => {
+            draw_profiles(f, app);
+            draw_delete(f, app);
+        }
+        Screen::Rename => {
+            draw_profiles(f, app);
+            draw_rename(f, app);
+        }
+        Screen::Clone => {
+            draw_profiles(f, app);
+            draw_clone(f, app);
+        }
+        Screen::Snapshots => draw_snapshots(f, app),
+        Screen::Reset => {
+            draw_profiles(f, app);
+            draw_reset(f, app);
+        }
+        Screen::Usage => draw_usage(f, app),
+        Screen::Gc => draw_gc(f, app),
+        Screen::Props => draw_props(f, app),
+    }
+}
+
+fn draw_profiles(f: &mut Frame, app: &App) {
+    let banner_height = if app.consistency.is_some() { 4 } else { 0 };
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(4),
+            Constraint::Length(banner_height),
+            Constraint::Min(8),
+            Constraint::Length(9),
+            Constraint::Length(2),
+        ])
+        .split(f.size());
+
+    let title = Paragraph::new("Waydroid Universal Image Switcher")
+        .block(Block::default().borders(Borders::ALL).title("waydroid-switch"))
+        .style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD));
+    f.render_widget(title, chunks[0]);
+
+    if let Some(consistency) = &app.consistency {
+        let banner = Paragraph::new(format!(
+            "Live state is split across profiles: {}\nPress c to reconcile.",
+            consistency.summary()
+        ))
+        .block(Block::default().borders(Borders::ALL).title("Warning"))
+        .style(Style::default().fg(Color::Red).add_modifier(Modifier::BOLD))
+        .wrap(Wrap { trim: true });
+        f.render_widget(banner, chunks[1]);
+    }
+
+    let visible = app.visible_profiles();
+    let mut state = ListState::default();
+
+    let current = app.current_images_path.as_deref().unwrap_or("(unknown)");
+
+    let body = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(58), Constraint::Percentage(42)])
+        .split(chunks[2]);
+
+    let items: Vec<ListItem> = if app.tree_mode {
+        let rows = app.tree_rows();
+        if !rows.is_empty() {
+            state.select(Some(app.tree_cursor.min(rows.len() - 1)));
+        }
+        rows.iter()
+            .map(|row| tree_row_item(app, row, current))
+            .collect()
+    } else {
+        state.select(visible.iter().position(|(i, _)| *i == app.selected));
+        flat_items(app, &visible, current)
+    };
+
+    let list_title = if app.filter.is_empty() && !app.filter_editing {
+        "Profiles (auto-scanned from ~/waydroid-images)".to_string()
+    } else {
+        format!(
+            "Search: {}{}  ({} of {})",
+            app.filter,
+            if app.filter_editing { "_" } else { "" },
+            visible.len(),
+            app.profiles.len()
+        )
+    };
+
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(list_title))
+        .highlight_style(
+            Style::default()
+                .bg(Color::Blue)
+                .fg(Color::White)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("▶ ");
+
+    f.render_stateful_widget(list, body[0], &mut state);
+
+    let detail_lines = match (app.details.as_ref(), app.profiles.get(app.selected)) {
+        (Some(details), Some(profile)) => details.lines(profile).join("\n"),
+        _ => "No profile selected".to_string(),
+    };
+    let details = Paragraph::new(detail_lines)
+        .block(Block::default().borders(Borders::ALL).title("Details"))
+        .wrap(Wrap { trim: false });
+    f.render_widget(details, body[1]);
+
+    let status = Paragraph::new(format!(
+        "Current images_path: {}\nStatus: {}",
+        current, app.status
+    ))
+    .block(Block::default().borders(Borders::ALL).title("Status"))
+    .wrap(Wrap { trim: true });
+    f.render_widget(status, chunks[3]);
+
+    let help_text = if app.filter_editing {
+        "Type to filter  Up/Down: move  Backspace: delete  Enter: keep filter  Esc: clear"
+    } else if app.tree_mode {
+        "Up/Down: move  Left/Right: collapse/expand  Enter: switch  t: flat list  /: search  q: quit"
+    } else {
+        "Up/Down: move  Enter: switch  /: search  t: tree  p: previous  a: manual add  m: rename  C: clone  s: snapshots  R: reset  d: delete  P: properties  u: disk usage  g: orphans  h: history  r: refresh  q: quit"
+    };
+    let help = Paragraph::new(help_text).style(Style::default().fg(Color::Yellow));
+    f.render_widget(help, chunks[4]);
+}
+
+fn flat_items<'a>(
+    app: &'a App,
+    visible: &[(usize, Option<ProfileMatch>)],
+    current: &str,
+) -> Vec<ListItem<'a>> {
+    visible
+        .iter()
+        .map(|(idx, found)| {
+            let p = &app.profiles[*idx];
+            let active = p.path.to_string_lossy() == current;
+            let marker = if active { "[active]" } else { "        " };
+            let hits = |field| {
+                found
+                    .as_ref()
+                    .filter(|m| m.field == field)
+                    .map(|m| m.indices.as_slice())
+            };
+
+            let mut spans = vec![Span::raw(format!("{} ", marker))];
+            spans.extend(highlight_matches(&p.name, hits(MatchField::Name)));
+            spans.push(Span::raw(" -> "));
+            spans.extend(highlight_matches(
+                &p.path.to_string_lossy(),
+                hits(MatchField::Path),
+            ));
+            if let Some(indices) = hits(MatchField::Meta) {
+                spans.push(Span::raw("  "));
+                spans.extend(highlight_matches(&p.meta.search_text(), Some(indices)));
+            }
+            ListItem::new(Line::from(spans))
+        })
+        .collect()
+}
+
+fn tree_row_item<'a>(app: &'a App, row: &TreeRow, current: &str) -> ListItem<'a> {
+    let indent = "  ".repeat(row.depth);
+    let active = row
+        .profile
+        .map(|idx| app.profiles[idx].path.to_string_lossy() == current)
+        .unwrap_or(false);
+    let marker = if active { "[active]" } else { "        " };
+
+    let text = match (&row.folder, row.expanded) {
+        (Some(_), true) => format!("{} {}▾ {}/ ({})", marker, indent, row.label, row.count),
+        (Some(_), false) => format!("{} {}▸ {}/ ({})", marker, indent, row.label, row.count),
+        (None, _) => format!("{} {}  {}", marker, indent, row.label),
+    };
+    let style = if row.folder.is_some() && row.profile.is_none() {
+        Style::default().fg(Color::Cyan)
+    } else {
+        Style::default()
+    };
+    ListItem::new(text).style(style)
+}
+
+fn highlight_matches(text: &str, indices: Option<&[usize]>) -> Vec<Span<'static>> {
+    let Some(indices) = indices else {
+        return vec![Span::raw(text.to_string())];
+    };
+    let hit = Style::default()
+        .fg(Color::Yellow)
+        .add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+    text.chars()
+        .enumerate()
+        .map(|(i, c)| {
+            if indices.contains(&i) {
+                Span::styled(c.to_string(), hit)
+            } else {
+                Span::raw(c.to_string())
+            }
+        })
+        .collect()
+}
+
+fn draw_confirm(f: &mut Frame, app: &App) {
+    let Some(pending) = &app.pending else {
+        return;
+    };
+
+    let current = app.current_images_path.as_deref().unwrap_or("(unknown)");
+    let from = app
+        .profiles
+        .iter()
+        .find(|p| p.path.to_string_lossy() == current)
+        .map(|p| p.name.as_str())
+        .unwrap_or(current);
+    let (session, session_style) = match pending.session_running {
+        Some(true) => (
+            "RUNNING - it will be stopped and unsaved work in Android apps is lost",
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        ),
+        Some(false) => ("stopped", Style::default().fg(Color::Green)),
+        None => ("unknown (waydroid status failed)", Style::default().fg(Color::Yellow)),
+    };
+
+    let text = vec![
+        Line::from(format!("From: {}", from)),
+        Line::from(format!("To:   {}", pending.target.name)),
+        Line::from(format!("      {}", pending.target.path.display())),
+        Line::from(""),
+        Line::from(vec![Span::raw("Session: "), Span::styled(session, session_style)]),
+        Line::from(""),
+        Line::from("Enter/y: switch  Esc/n: cancel"),
+    ];
+
+    let area = centered_rect(70, 11, f.size());
+    let popup = Paragraph::new(text)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Confirm switch")
+                .style(Style::default().fg(Color::White)),
+        )
+        .wrap(Wrap { trim: false });
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+}
+
+fn draw_delete(f: &mut Frame, app: &App) {
+    let Some(plan) = &app.delete_plan else {
+        return;
+    };
+
+    let mut text: Vec<Line> = plan.summary_lines().into_iter().map(Line::from).collect();
+    text.push(Line::from(""));
+    text.push(Line::from(format!(
+        "Also remove userdata and overlays: {}",
+        if app.delete_with_store { "yes" } else { "no" }
+    )));
+    text.push(Line::from(Span::styled(
+        format!(
+            "Space reclaimed: {}",
+            human_size_kb(plan.reclaimed_kb(app.delete_with_store))
+        ),
+        Style::default().add_modifier(Modifier::BOLD),
+    )));
+    text.push(Line::from(""));
+    text.push(Line::from("Enter/y: delete  s: toggle userdata/overlays  Esc/n: cancel"));
+
+    let area = centered_rect(80, 12, f.size());
+    let popup = Paragraph::new(text)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Delete profile")
+                .style(Style::default().fg(Color::Red)),
+        )
+        .wrap(Wrap { trim: false });
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+}
+
+fn draw_reset(f: &mut Frame, app: &App) {
+    let Some(profile) = app.profiles.get(app.selected) else {
+        return;
+    };
+    let size_of = |name: &str| {
+        app.details
+            .as_ref()
+            .and_then(|d| d.store_dirs.iter().find(|(n, _, _)| *n == name))
+            .map(|(_, _, size)| match size {
+                DirSize::Known(kb) => human_size_kb(*kb),
+                DirSize::Measuring => "measuring...".to_string(),
+                DirSize::Unknown => "size unknown".to_string(),
+                DirSize::Missing => "empty".to_string(),
+            })
+            .unwrap_or_else(|| "empty".to_string())
+    };
+    let mark = |on: bool| if on { "[x]" } else { "[ ]" };
+    let active =
+        app.current_images_path.as_deref() == Some(profile.path.to_string_lossy().as_ref());
+
+    let text = vec![
+        Line::from(format!("Profile: {}", profile.name)),
+        Line::from(if active {
+            "Active profile: the session is stopped and left stopped."
+        } else {
+            "Wiped directories are recreated empty, as on first boot."
+        }),
+        Line::from(""),
+        Line::from(format!(
+            "{} u: userdata ({})",
+            mark(app.reset_data),
+            size_of("data")
+        )),
+        Line::from(format!(
+            "{} o: overlays ({} + {})",
+            mark(app.reset_overlays),
+            size_of("overlay_rw"),
+            size_of("overlay_work")
+        )),
+        Line::from(""),
+        Line::from("y: reset  u/o: toggle  Esc/n: cancel"),
+    ];
+
+    let area = centered_rect(70, 9, f.size());
+    let popup = Paragraph::new(text).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Factory reset")
+            .style(Style::default().fg(Color::Red)),
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+}
+
+fn draw_rename(f: &mut Frame, app: &App) {
+    let Some(profile) = app.profiles.get(app.selected) else {
+        return;
+    };
+    let text = vec![
+        Line::from(format!("Profile: {}", profile.name)),
+        Line::from("Relative to ~/waydroid-images, '/' moves into subfolders."),
+        Line::from("Userdata, overlays and live links move along."),
+        Line::from(""),
+        Line::from(format!("{}: {}", app.rename.label, app.rename.value)),
+        Line::from(""),
+        Line::from("Enter: rename  Esc: cancel"),
+    ];
+
+    let area = centered_rect(70, 9, f.size());
+    let popup = Paragraph::new(text).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Rename / move profile"),
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+
+    let x = area.x + 1 + app.rename.label.len() as u16 + 2 + app.rename.cursor_col();
+    if x < area.x + area.width - 1 {
+        f.set_cursor(x, area.y + 5);
+    }
+}
+
+fn draw_clone(f: &mut Frame, app: &App) {
+    let Some(profile) = app.profiles.get(app.selected) else {
+        return;
+    };
+    let active =
+        app.current_images_path.as_deref() == Some(profile.path.to_string_lossy().as_ref());
+    let images = if app.clone_copy_images {
+        "copy (reflink where supported)"
+    } else {
+        "symlink to the source images"
+    };
+    let text = vec![
+        Line::from(format!("Source: {}", profile.name)),
+        Line::from(if active {
+            "Active profile: the session is stopped for a consistent copy."
+        } else {
+            "Userdata and overlays are copied along."
+        }),
+        Line::from(format!("Images: {}", images)),
+        Line::from(format!("{}: {}", app.clone_name.label, app.clone_name.value)),
+        Line::from(""),
+        Line::from("Enter: clone  Tab: link/copy images  Esc: cancel"),
+    ];
+
+    let area = centered_rect(70, 8, f.size());
+    let popup = Paragraph::new(text).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Clone profile"),
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+
+    let x = area.x + 1 + app.clone_name.label.len() as u16 + 2 + app.clone_name.cursor_col();
+    if x < area.x + area.width - 1 {
+        f.set_cursor(x, area.y + 4);
+    }
+}
+
+fn centered_rect(percent_x: u16, height: u16, area: Rect) -> Rect {
+    let width = area.width * percent_x / 100;
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + area.height.saturating_sub(height) / 2,
+        width,
+        height: height.min(area.height),
+    }
+}
+
+fn draw_reconcile(f: &mut Frame, app: &App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(4),
+            Constraint::Length(6),
+            Constraint::Min(6),
+            Constraint::Length(5),
+            Constraint::Length(2),
+        ])
+        .split(f.size());
+
+    let title = Paragraph::new("Reconcile Live State")
+        .block(Block::default().borders(Borders::ALL).title("waydroid-switch"))
+        .style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD));
+    f.render_widget(title, chunks[0]);
+
+    let entries = app
+        .consistency
+        .as_ref()
+        .map(|c| c.summary().replace(", ", "\n"))
+        .unwrap_or_default();
+    let mismatch = Paragraph::new(entries)
+        .block(Block::default().borders(Borders::ALL).title("Current state"))
+        .style(Style::default().fg(Color::Red));
+    f.render_widget(mismatch, chunks[1]);
+
+    let candidates = app
+        .consistency
+        .as_ref()
+        .map(|c| c.candidates(&app.profiles))
+        .unwrap_or_default();
+    let items: Vec<ListItem> = if candidates.is_empty() {
+        vec![ListItem::new("No referenced profile exists anymore; switch from the main list instead")]
+    } else {
+        candidates
+            .iter()
+            .map(|(idx, labels)| {
+                ListItem::new(format!(
+                    "{} (used by {})",
+                    app.profiles[*idx].name,
+                    labels.join(", ")
+                ))
+            })
+            .collect()
+    };
+
+    let mut state = ListState::default();
+    if !candidates.is_empty() {
+        state.select(Some(app.reconcile_selected));
+    }
 
-    fs::create_dir_all(&profile_root)?;
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title("Reconcile to profile"))
+        .highlight_style(
+            Style::default()
+                .bg(Color::Blue)
+                .fg(Color::White)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("▶ ");
+    f.render_stateful_widget(list, chunks[2], &mut state);
 
-    if overlay_rw_live.exists() && !rw_is_link && !profile_overlay_rw.exists() {
-        run_cmd("sudo", &["mv", "/var/lib/waydroid/overlay_rw", &profile_overlay_rw_s])?;
-        logs.push("overlay migration: moved legacy overlay_rw into active profile".to_string());
-    }
-    if overlay_work_live.exists() && !work_is_link && !profile_overlay_work.exists() {
-        run_cmd("sudo", &["mv", "/var/lib/waydroid/overlay_work", &profile_overlay_work_s])?;
-        logs.push("overlay migration: moved legacy overlay_work into active profile".to_string());
-    }
+    let status = Paragraph::new(format!("Status: {}", app.status))
+        .block(Block::default().borders(Borders::ALL).title("Status"))
+        .wrap(Wrap { trim: true });
+    f.render_widget(status, chunks[3]);
 
-    Ok(())
+    let help = Paragraph::new("Up/Down: move  Enter: switch everything to profile  Esc: back")
+        .style(Style::default().fg(Color::Yellow));
+    f.render_widget(help, chunks[4]);
 }
 
-fn setup_profile_overlays(path: &Path, logs: &mut Vec<String>) -> Result<()> {
-    let home = home_dir().context("Failed to resolve HOME")?;
-    let profile_id = profile_id_from_path(path, &home);
-    let profile_root = home
-        .join(".local/share/waydroid/profiles")
-        .join(&profile_id);
-    let profile_overlay_rw = profile_root.join("overlay_rw");
-    let profile_overlay_work = profile_root.join("overlay_work");
+fn draw_history(f: &mut Frame, app: &App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(4),
+            Constraint::Min(6),
+            Constraint::Length(12),
+            Constraint::Length(2),
+        ])
+        .split(f.size());
 
-    fs::create_dir_all(profile_overlay_rw.join("system"))?;
-    fs::create_dir_all(profile_overlay_rw.join("vendor"))?;
-    fs::create_dir_all(profile_overlay_work.join("system"))?;
-    fs::create_dir_all(profile_overlay_work.join("vendor"))?;
+    let title = Paragraph::new("Switch History")
+        .block(Block::default().borders(Borders::ALL).title("waydroid-switch"))
+        .style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD));
+    f.render_widget(title, chunks[0]);
 
-    let profile_overlay_rw_s = profile_overlay_rw.to_string_lossy().to_string();
-    let profile_overlay_work_s = profile_overlay_work.to_string_lossy().to_string();
+    let items: Vec<ListItem> = app
+        .history
+        .iter()
+        .map(|r| {
+            let style = if r.succeeded() {
+                Style::default()
+            } else {
+                Style::default().fg(Color::Red)
+            };
+            ListItem::new(r.summary()).style(style)
+        })
+        .collect();
 
-    run_cmd("sudo", &["rm", "-rf", "/var/lib/waydroid/overlay_rw"])?;
-    run_cmd("sudo", &["rm", "-rf", "/var/lib/waydroid/overlay_work"])?;
-    run_cmd(
-        "sudo",
-        &["ln", "-s", &profile_overlay_rw_s, "/var/lib/waydroid/overlay_rw"],
-    )?;
-    run_cmd(
-        "sudo",
-        &[
-            "ln",
-            "-s",
-            &profile_overlay_work_s,
-            "/var/lib/waydroid/overlay_work",
-        ],
-    )?;
+    let mut state = ListState::default();
+    if !app.history.is_empty() {
+        state.select(Some(app.history_selected));
+    }
 
-    logs.push(format!(
-        "overlay: active profile '{}' -> {}",
-        profile_id,
-        profile_root.display()
-    ));
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title("Newest first"))
+        .highlight_style(
+            Style::default()
+                .bg(Color::Blue)
+                .fg(Color::White)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("▶ ");
+    f.render_stateful_widget(list, chunks[1], &mut state);
 
-    Ok(())
-}
+    let details = app
+        .history
+        .get(app.history_selected)
+        .map(|r| r.logs.join("\n"))
+        .unwrap_or_else(|| app.status.clone());
+    let logs = Paragraph::new(details)
+        .block(Block::default().borders(Borders::ALL).title("Switch log"))
+        .wrap(Wrap { trim: true });
+    f.render_widget(logs, chunks[2]);
 
-fn symlink_path_points_somewhere(path: &Path) -> bool {
-    match fs::symlink_metadata(path) {
-        Ok(meta) => meta.file_type().is_symlink(),
-        Err(_) => false,
-    }
+    let help = Paragraph::new("Up/Down: move  Esc: back").style(Style::default().fg(Color::Yellow));
+    f.render_widget(help, chunks[3]);
 }
 
-fn profile_id_from_path(path: &Path, home: &Path) -> String {
-    let base = home.join("waydroid-images");
-    let raw = if let Ok(rel) = path.strip_prefix(&base) {
-        rel.to_string_lossy().to_string()
-    } else {
-        path.to_string_lossy().to_string()
-    };
+fn draw_snapshots(f: &mut Frame, app: &App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(4),
+            Constraint::Min(6),
+            Constraint::Length(10),
+            Constraint::Length(2),
+        ])
+        .split(f.size());
 
-    let mut hasher = DefaultHasher::new();
-    raw.hash(&mut hasher);
-    let hash = hasher.finish();
+    let name = app
+        .profiles
+        .get(app.selected)
+        .map(|p| p.name.as_str())
+        .unwrap_or("");
+    let title = Paragraph::new(format!("Snapshots: {}", name))
+        .block(Block::default().borders(Borders::ALL).title("waydroid-switch"))
+        .style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD));
+    f.render_widget(title, chunks[0]);
 
-    let mut cleaned = raw
-        .chars()
-        .map(|c| {
-            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
-                c
-            } else {
-                '_'
-            }
-        })
-        .collect::<String>();
-    while cleaned.contains("__") {
-        cleaned = cleaned.replace("__", "_");
+    let items: Vec<ListItem> = app
+        .snapshots
+        .iter()
+        .map(|s| ListItem::new(s.summary()))
+        .collect();
+    let mut state = ListState::default();
+    if !app.snapshots.is_empty() {
+        state.select(Some(app.snapshot_selected));
     }
-    cleaned = cleaned.trim_matches('_').to_string();
-    if cleaned.is_empty() {
-        cleaned = "profile".to_string();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title("Newest first"))
+        .highlight_style(
+            Style::default()
+                .bg(Color::Blue)
+                .fg(Color::White)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("▶ ");
+    f.render_stateful_widget(list, chunks[1], &mut state);
+
+    if app.snapshot_naming {
+        let input = Paragraph::new(format!(
+            "{}: {}",
+            app.snapshot_name.label, app.snapshot_name.value
+        ))
+        .block(Block::default().borders(Borders::ALL).title("New snapshot"));
+        f.render_widget(input, chunks[2]);
+        let x = chunks[2].x
+            + 1
+            + app.snapshot_name.label.len() as u16
+            + 2
+            + app.snapshot_name.cursor_col();
+        f.set_cursor(x, chunks[2].y + 1);
+    } else {
+        let status = Paragraph::new(app.status.as_str())
+            .block(Block::default().borders(Borders::ALL).title("Status"))
+            .wrap(Wrap { trim: true });
+        f.render_widget(status, chunks[2]);
     }
-    format!("{}_{:08x}", cleaned, (hash & 0xffff_ffff))
+
+    let help = Paragraph::new(
+        "Up/Down: move  n: new snapshot  Enter: restore  x: delete  Esc: back",
+    )
+    .style(Style::default().fg(Color::Yellow));
+    f.render_widget(help, chunks[3]);
 }
 
-fn run_cmd(cmd: &str, args: &[&str]) -> Result<String> {
-    let out = Command::new(cmd)
-        .args(args)
-        .output()
-        .with_context(|| format!("Failed to run: {} {}", cmd, args.join(" ")))?;
+fn draw_props(f: &mut Frame, app: &App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(4),
+            Constraint::Min(6),
+            Constraint::Length(10),
+            Constraint::Length(2),
+        ])
+        .split(f.size());
 
-    if out.status.success() {
-        let stdout = String::from_utf8_lossy(&out.stdout).trim().to_string();
-        if stdout.is_empty() {
-            return Ok("ok".to_string());
-        }
-        return Ok(stdout);
-    }
+    let active = app
+        .current_images_path
+        .as_deref()
+        .and_then(|path| app.profiles.iter().find(|p| p.path == Path::new(path)))
+        .map(|p| p.name.as_str())
+        .unwrap_or("unknown profile");
+    let title = Paragraph::new(format!("Properties: {}", active))
+        .block(Block::default().borders(Borders::ALL).title("waydroid-switch"))
+        .style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD));
+    f.render_widget(title, chunks[0]);
 
-    let stderr = String::from_utf8_lossy(&out.stderr).trim().to_string();
-    let stdout = String::from_utf8_lossy(&out.stdout).trim().to_string();
-    let msg = if !stderr.is_empty() {
-        stderr
-    } else if !stdout.is_empty() {
-        stdout
+    let items: Vec<ListItem> = app
+        .props
+        .iter()
+        .map(|p| ListItem::new(p.summary()))
+        .collect();
+    let mut state = ListState::default();
+    if !app.props.is_empty() {
+        state.select(Some(app.prop_selected));
+    }
+    let list_title = if app.props_loading {
+        "loading... cfg: waydroid.cfg [properties]  persist: running session"
     } else {
-        "command failed".to_string()
+        "cfg: waydroid.cfg [properties]  persist: running session"
     };
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(list_title))
+        .highlight_style(
+            Style::default()
+                .bg(Color::Blue)
+                .fg(Color::White)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("▶ ");
+    f.render_stateful_widget(list, chunks[1], &mut state);
 
-    bail!("{} {} -> {}", cmd, args.join(" "), msg)
-}
-
-fn draw(f: &mut Frame, app: &App) {
-    match app.screen {
-        Screen::Profiles => draw_profiles(f, app),
-        Screen::ManualAdd => draw_manual_add(f, app),
+    if let Some(edit) = &app.prop_edit {
+        let title = match edit {
+            PropEdit::NewKey => format!(
+                "New property in {} (Tab: switch to {})",
+                app.prop_new_source.label(),
+                match app.prop_new_source {
+                    PropSource::Cfg => PropSource::Persist.label(),
+                    PropSource::Persist => PropSource::Cfg.label(),
+                }
+            ),
+            PropEdit::Value { key, source } => {
+                let overridden = app
+                    .props
+                    .iter()
+                    .any(|p| p.key == *key && p.source == *source && p.profile_override);
+                format!(
+                    "{} ({}){}",
+                    key,
+                    source.label(),
+                    if overridden {
+                        ", also saved to the profile's profile.cfg"
+                    } else {
+                        ""
+                    }
+                )
+            }
+        };
+        let input = Paragraph::new(format!("{}: {}", app.prop_field.label, app.prop_field.value))
+            .block(Block::default().borders(Borders::ALL).title(title));
+        f.render_widget(input, chunks[2]);
+        let x = chunks[2].x
+            + 1
+            + app.prop_field.label.len() as u16
+            + 2
+            + app.prop_field.cursor_col();
+        f.set_cursor(x, chunks[2].y + 1);
+    } else {
+        let status = Paragraph::new(app.status.as_str())
+            .block(Block::default().borders(Borders::ALL).title("Status"))
+            .wrap(Wrap { trim: true });
+        f.render_widget(status, chunks[2]);
     }
+
+    let help = Paragraph::new(
+        "Up/Down: move  Enter/e: edit  n: new  x: remove  r: reload  Esc: back",
+    )
+    .style(Style::default().fg(Color::Yellow));
+    f.render_widget(help, chunks[3]);
 }
 
-fn draw_profiles(f: &mut Frame, app: &App) {
+fn draw_gc(f: &mut Frame, app: &App) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .margin(1)
         .constraints([
             Constraint::Length(4),
-            Constraint::Min(8),
-            Constraint::Length(9),
+            Constraint::Min(6),
+            Constraint::Length(8),
             Constraint::Length(2),
         ])
         .split(f.size());
 
-    let title = Paragraph::new("Waydroid Universal Image Switcher")
+    let target = app
+        .profiles
+        .get(app.gc_target)
+        .map(|p| p.name.as_str())
+        .unwrap_or("");
+    let title = Paragraph::new(format!("Orphaned Profile Data\nAttach target: {}", target))
         .block(Block::default().borders(Borders::ALL).title("waydroid-switch"))
         .style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD));
     f.render_widget(title, chunks[0]);
 
+    let items: Vec<ListItem> = app
+        .orphans
+        .iter()
+        .map(|o| ListItem::new(o.summary()))
+        .collect();
     let mut state = ListState::default();
-    state.select(Some(app.selected));
+    if !app.orphans.is_empty() {
+        state.select(Some(app.orphan_selected));
+    }
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title("Largest first"))
+        .highlight_style(
+            Style::default()
+                .bg(Color::Blue)
+                .fg(Color::White)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("▶ ");
+    f.render_stateful_widget(list, chunks[1], &mut state);
 
-    let current = app.current_images_path.as_deref().unwrap_or("(unknown)");
+    let status = Paragraph::new(app.status.as_str())
+        .block(Block::default().borders(Borders::ALL).title("Status"))
+        .wrap(Wrap { trim: true });
+    f.render_widget(status, chunks[2]);
+
+    let help = Paragraph::new(
+        "Up/Down: move  Left/Right: target  a: attach  w: swap  m: merge  x: delete  r: rescan  Esc: back",
+    )
+    .style(Style::default().fg(Color::Yellow));
+    f.render_widget(help, chunks[3]);
+}
+
+fn draw_usage(f: &mut Frame, app: &App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(4),
+            Constraint::Min(6),
+            Constraint::Length(8),
+            Constraint::Length(2),
+        ])
+        .split(f.size());
+
+    let title = Paragraph::new("Disk Usage")
+        .block(Block::default().borders(Borders::ALL).title("waydroid-switch"))
+        .style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD));
+    f.render_widget(title, chunks[0]);
 
     let items: Vec<ListItem> = app
-        .profiles
+        .usage
         .iter()
-        .map(|p| {
-            let active = p.path.to_string_lossy() == current;
-            let marker = if active { "[active]" } else { "        " };
-            ListItem::new(format!("{} {} -> {}", marker, p.name, p.path.display()))
-        })
+        .flat_map(|u| u.profiles.iter())
+        .map(|row| ListItem::new(UsageReport::row(row)))
         .collect();
-
+    let mut state = ListState::default();
+    if !items.is_empty() {
+        state.select(Some(app.usage_selected));
+    }
     let list = List::new(items)
         .block(
             Block::default()
                 .borders(Borders::ALL)
-                .title("Profiles (auto-scanned from ~/waydroid-images)"),
+                .title(format!("  {}", UsageReport::header())),
         )
         .highlight_style(
             Style::default()
@@ -724,18 +2932,18 @@ fn draw_profiles(f: &mut Frame, app: &App) {
                 .add_modifier(Modifier::BOLD),
         )
         .highlight_symbol("▶ ");
-
     f.render_stateful_widget(list, chunks[1], &mut state);
 
-    let status = Paragraph::new(format!(
-        "Current images_path: {}\nStatus: {}",
-        current, app.status
-    ))
-    .block(Block::default().borders(Borders::ALL).title("Status"))
-    .wrap(Wrap { trim: true });
-    f.render_widget(status, chunks[2]);
+    let totals = match &app.usage {
+        Some(report) => report.totals_lines().join("\n"),
+        None => app.status.clone(),
+    };
+    let totals = Paragraph::new(totals)
+        .block(Block::default().borders(Borders::ALL).title("Totals"))
+        .wrap(Wrap { trim: true });
+    f.render_widget(totals, chunks[2]);
 
-    let help = Paragraph::new("Up/Down: move  Enter: switch  a: manual add  r: refresh  q: quit")
+    let help = Paragraph::new("Up/Down: move  Enter: select profile  r: recompute  Esc: back")
         .style(Style::default().fg(Color::Yellow));
     f.render_widget(help, chunks[3]);
 }
@@ -791,7 +2999,7 @@ fn draw_manual_add(f: &mut Frame, app: &App) {
 
     if app.manual.selected < 3 {
         let field = &app.manual.fields[app.manual.selected];
-        let x = chunks[1].x + 4 + field.label.len() as u16 + 2 + field.cursor as u16;
+        let x = chunks[1].x + 4 + field.label.len() as u16 + 2 + field.cursor_col();
         let y = chunks[1].y + 1 + app.manual.selected as u16;
         if x < chunks[1].x + chunks[1].width {
             f.set_cursor(x, y);
@@ -812,3 +3020,25 @@ fn restore_terminal(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> Re
     terminal.show_cursor()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn field_edits_whole_chars() {
+        let mut field = Field::with_value("Name", "café".to_string());
+        field.backspace();
+        assert_eq!(field.value, "caf");
+        field.insert_char('é');
+        field.move_left();
+        field.move_left();
+        assert_eq!(field.cursor_col(), 2);
+        field.insert_char('ß');
+        assert_eq!(field.value, "caßfé");
+        field.move_right();
+        field.move_right();
+        field.move_right();
+        assert_eq!(field.cursor, field.value.len());
+    }
+}
@@ -0,0 +1,142 @@
+use anyhow::Result;
+use std::path::Path;
+
+use crate::{
+    cfg::Ini,
+    state::{SavedCfgValue, SwitchState},
+    write_waydroid_cfg, ProfileMeta, WAYDROID_CFG,
+};
+
+/// waydroid.cfg sections a `profile.cfg` may override.
+pub const SECTIONS: [&str; 2] = ["waydroid", "properties"];
+
+/// Keys the switch itself manages; overriding them would break it.
+const RESERVED: [&str; 1] = ["images_path"];
+
+/// Puts back what the previous profile's overrides replaced, then applies
+/// the `[waydroid]`/`[properties]` entries of `path`'s `profile.cfg`,
+/// remembering the values they replace. Returns whether `[properties]`
+/// changed.
+pub fn apply(path: &Path, logs: &mut Vec<String>) -> Result<bool> {
+    let mut state = SwitchState::load();
+    let overrides = ProfileMeta::load(path).cfg_overrides;
+    if state.cfg_restore.is_empty() && overrides.is_empty() {
+        return Ok(false);
+    }
+
+    let mut ini = Ini::load(Path::new(WAYDROID_CFG))?;
+    let before = ini.section("properties");
+    restore(&mut ini, std::mem::take(&mut state.cfg_restore), logs);
+    state.cfg_restore = override_values(&mut ini, &overrides, logs);
+
+    write_waydroid_cfg(&ini)?;
+    state.save()?;
+    Ok(ini.section("properties") != before)
+}
+
+/// Only puts back what the current profile's overrides replaced, leaving
+/// waydroid.cfg as it would be without the switcher. Returns whether
+/// `[properties]` changed.
+pub fn clear(logs: &mut Vec<String>) -> Result<bool> {
+    let mut state = SwitchState::load();
+    if state.cfg_restore.is_empty() {
+        return Ok(false);
+    }
+
+    let mut ini = Ini::load(Path::new(WAYDROID_CFG))?;
+    let before = ini.section("properties");
+    restore(&mut ini, std::mem::take(&mut state.cfg_restore), logs);
+
+    write_waydroid_cfg(&ini)?;
+    state.save()?;
+    Ok(ini.section("properties") != before)
+}
+
+/// Newest first, so a key overridden twice ends up at its original value.
+fn restore(ini: &mut Ini, saved: Vec<SavedCfgValue>, logs: &mut Vec<String>) {
+    for saved in saved.into_iter().rev() {
+        match &saved.value {
+            Some(value) => ini.set(&saved.section, &saved.key, value),
+            None => {
+                ini.remove(&saved.section, &saved.key);
+            }
+        }
+        logs.push(format!(
+            "cfg override: restored [{}] {} = {}",
+            saved.section,
+            saved.key,
+            saved.value.as_deref().unwrap_or("(unset)")
+        ));
+    }
+}
+
+/// Sets each (section, key, value) and returns what it replaced.
+fn override_values(
+    ini: &mut Ini,
+    overrides: &[(String, String, String)],
+    logs: &mut Vec<String>,
+) -> Vec<SavedCfgValue> {
+    let mut saved = Vec::new();
+    for (section, key, value) in overrides {
+        if section == "waydroid" && RESERVED.contains(&key.as_str()) {
+            logs.push(format!(
+                "cfg override warning: [{}] {} ignored",
+                section, key
+            ));
+            continue;
+        }
+        saved.push(SavedCfgValue {
+            value: ini.get(section, key),
+            section: section.clone(),
+            key: key.clone(),
+        });
+        ini.set(section, key, value);
+        logs.push(format!("cfg override: [{}] {} = {}", section, key, value));
+    }
+    saved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(section: &str, key: &str, value: &str) -> (String, String, String) {
+        (section.to_string(), key.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn restore_undoes_overrides_including_repeated_keys() {
+        let original = "[waydroid]\nmount_overlays = True\n\n[properties]\nro.a = 1\n";
+        let mut ini = Ini::parse(original);
+        let mut logs = Vec::new();
+        let saved = override_values(
+            &mut ini,
+            &[
+                entry("waydroid", "mount_overlays", "False"),
+                entry("properties", "ro.a", "2"),
+                entry("properties", "ro.a", "3"),
+                entry("properties", "persist.waydroid.width", "1920"),
+            ],
+            &mut logs,
+        );
+        assert_eq!(ini.get("properties", "ro.a").as_deref(), Some("3"));
+        assert_eq!(saved[2].value.as_deref(), Some("2"));
+        assert_eq!(saved[3].value, None);
+
+        restore(&mut ini, saved, &mut logs);
+        assert_eq!(ini.render(), original);
+    }
+
+    #[test]
+    fn images_path_is_never_overridden() {
+        let mut ini = Ini::parse("[waydroid]\nimages_path = /a\n");
+        let mut logs = Vec::new();
+        let saved = override_values(
+            &mut ini,
+            &[entry("waydroid", "images_path", "/b")],
+            &mut logs,
+        );
+        assert!(saved.is_empty());
+        assert_eq!(ini.get("waydroid", "images_path").as_deref(), Some("/a"));
+    }
+}
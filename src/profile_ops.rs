@@ -0,0 +1,618 @@
+use anyhow::{bail, Context, Result};
+use dirs::home_dir;
+use std::{
+    fs,
+    os::unix::fs::symlink,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    current_images_path, discover_profiles, disk_usage_kb, human_size_kb, lock,
+    profile_id_from_path, profiles_root, run_cmd, session_left_stopped, set_images_path,
+    setup_profile_overlays, setup_profile_userdata,
+    snapshot::{self, snapshots_root, stop_if_running, Snapshot},
+    state::SwitchState,
+    stop_session, ImageProfile, PROFILE_META_FILE, STORE_DIRS,
+};
+
+/// What deleting a profile would remove, gathered up front so the user can
+/// see the space reclaimed before confirming.
+#[derive(Clone, Debug)]
+pub struct DeletePlan {
+    pub profile: ImageProfile,
+    /// Both images are symlinks (manual add), so only the links go.
+    pub links_only: bool,
+    /// Other profiles live below this folder, so only its own files go.
+    pub has_nested: bool,
+    pub folder_kb: Option<u64>,
+    pub store: PathBuf,
+    pub store_kb: Option<u64>,
+    /// Removed together with the store.
+    pub snapshots: Vec<Snapshot>,
+    pub snapshots_kb: Option<u64>,
+}
+
+impl DeletePlan {
+    pub fn new(profile: &ImageProfile) -> Result<Self> {
+        refuse_active(&profile.path, "delete")?;
+        let home = home_dir().context("Failed to resolve HOME")?;
+        let id = profile_id_from_path(&profile.path, &home);
+        let store = profiles_root(&home).join(&id);
+        let snapshot_dir = snapshots_root(&home).join(&id);
+
+        let links_only = ["system.img", "vendor.img"]
+            .iter()
+            .all(|name| profile.path.join(name).is_symlink());
+        let has_nested = contains_nested_profile(&profile.path);
+        let folder_kb = if links_only {
+            Some(0)
+        } else if has_nested {
+            image_files_kb(&profile.path)
+        } else {
+            disk_usage_kb(&profile.path)
+        };
+        let store_kb = if store.exists() {
+            disk_usage_kb(&store)
+        } else {
+            None
+        };
+        let snapshots_kb = if snapshot_dir.exists() {
+            disk_usage_kb(&snapshot_dir)
+        } else {
+            Some(0)
+        };
+
+        Ok(Self {
+            profile: profile.clone(),
+            links_only,
+            has_nested,
+            folder_kb,
+            store,
+            store_kb,
+            snapshots: snapshot::list(profile)?,
+            snapshots_kb,
+        })
+    }
+
+    pub fn summary_lines(&self) -> Vec<String> {
+        let images = if self.links_only {
+            "image symlinks only (targets are kept)".to_string()
+        } else if self.has_nested {
+            format!(
+                "system.img/vendor.img ({}), nested profiles are kept",
+                size_or_unknown(self.folder_kb)
+            )
+        } else {
+            format!("whole folder ({})", size_or_unknown(self.folder_kb))
+        };
+        let store = if self.store.exists() {
+            format!(
+                "{} ({})",
+                self.store.display(),
+                size_or_unknown(self.store_kb)
+            )
+        } else {
+            "none".to_string()
+        };
+        vec![
+            format!("Profile: {}", self.profile.name),
+            format!("Folder:  {}", self.profile.path.display()),
+            format!("Images:  {}", images),
+            format!("Userdata + overlays: {}", store),
+            format!(
+                "Snapshots: {} ({}, go with the userdata)",
+                self.snapshots.len(),
+                size_or_unknown(self.snapshots_kb)
+            ),
+        ]
+    }
+
+    pub fn reclaimed_kb(&self, with_store: bool) -> u64 {
+        let store = if with_store {
+            self.store_kb.unwrap_or(0) + self.snapshots_kb.unwrap_or(0)
+        } else {
+            0
+        };
+        self.folder_kb.unwrap_or(0) + store
+    }
+
+    pub fn execute(&self, with_store: bool) -> Result<Vec<String>> {
+        let _lock = lock::acquire("delete")?;
+        refuse_active(&self.profile.path, "delete")?;
+        let mut logs = Vec::new();
+        let dir = &self.profile.path;
+
+        if self.links_only || self.has_nested || is_images_root(dir) {
+            for name in ["system.img", "vendor.img", PROFILE_META_FILE] {
+                let file = dir.join(name);
+                if file.is_symlink() || file.exists() {
+                    fs::remove_file(&file)
+                        .with_context(|| format!("Failed removing {}", file.display()))?;
+                    logs.push(format!("delete: removed {}", file.display()));
+                }
+            }
+            if !is_images_root(dir) && fs::remove_dir(dir).is_ok() {
+                logs.push(format!("delete: removed empty folder {}", dir.display()));
+            }
+        } else {
+            fs::remove_dir_all(dir)
+                .with_context(|| format!("Failed removing {}", dir.display()))?;
+            logs.push(format!("delete: removed folder {}", dir.display()));
+        }
+
+        if with_store && self.store.exists() {
+            // Overlay trees contain root-owned files written by the container.
+            let store_s = self.store.to_string_lossy().to_string();
+            run_cmd("sudo", &["rm", "-rf", &store_s])?;
+            logs.push(format!(
+                "delete: removed profile store {}",
+                self.store.display()
+            ));
+        }
+        if with_store {
+            for snapshot in &self.snapshots {
+                logs.extend(snapshot::remove(snapshot)?);
+            }
+            if let Some(dir) = self.snapshots.first().and_then(|s| s.path.parent()) {
+                let _ = fs::remove_dir(dir);
+            }
+        }
+
+        logs.push(format!(
+            "delete: reclaimed about {}",
+            human_size_kb(self.reclaimed_kb(with_store))
+        ));
+        Ok(logs)
+    }
+}
+
+/// `waydroid-switch delete <NAME> [--with-data] [-y]`
+pub fn run_delete(args: &[String]) -> Result<()> {
+    let with_store = args.iter().any(|a| a == "--with-data");
+    let yes = args.iter().any(|a| a == "-y" || a == "--yes");
+    let Some(name) = args.iter().find(|a| !a.starts_with('-')) else {
+        bail!("delete expects a profile name");
+    };
+    let profile = find_profile(name)?;
+    let plan = DeletePlan::new(&profile)?;
+
+    for line in plan.summary_lines() {
+        println!("{}", line);
+    }
+    println!(
+        "Space reclaimed: {}{}",
+        human_size_kb(plan.reclaimed_kb(with_store)),
+        if with_store {
+            ""
+        } else {
+            " (userdata and overlays kept, add --with-data to remove them)"
+        }
+    );
+    if !yes && !crate::prompt_yes_no("Delete this profile?")? {
+        bail!("Delete cancelled");
+    }
+    println!("{}", plan.execute(with_store)?.join("\n"));
+    Ok(())
+}
+
+/// Moves a profile folder to `new_name` (relative to `~/waydroid-images`)
+/// and carries the userdata/overlay stores of it and any nested profiles
+/// along, since their ids are derived from the folder path. Returns the new
+/// folder path.
+pub fn rename_profile(profile: &ImageProfile, new_name: &str) -> Result<(PathBuf, Vec<String>)> {
+    let _lock = lock::acquire("rename")?;
+    let home = home_dir().context("Failed to resolve HOME")?;
+    let store_root = profiles_root(&home);
+
+    if is_images_root(&profile.path) {
+        bail!("The top-level ~/waydroid-images profile cannot be moved");
+    }
+    let new_path = new_profile_path(&home, new_name)?;
+    if new_path.starts_with(&profile.path) {
+        bail!("Cannot move a profile into itself");
+    }
+
+    // Every profile at or below the folder changes id.
+    let moves: Vec<(PathBuf, PathBuf)> = discover_profiles()?
+        .into_iter()
+        .filter(|p| p.path.starts_with(&profile.path))
+        .map(|p| {
+            let rel = p.path.strip_prefix(&profile.path).unwrap_or(Path::new(""));
+            let new = if rel.as_os_str().is_empty() {
+                new_path.clone()
+            } else {
+                new_path.join(rel)
+            };
+            (p.path, new)
+        })
+        .collect();
+    let store_moves: Vec<(PathBuf, PathBuf)> = moves
+        .iter()
+        .map(|(old, new)| {
+            (
+                store_root.join(profile_id_from_path(old, &home)),
+                store_root.join(profile_id_from_path(new, &home)),
+            )
+        })
+        .filter(|(old, _)| old.exists())
+        .collect();
+    if let Some((_, taken)) = store_moves.iter().find(|(_, new)| new.exists()) {
+        bail!("Profile store {} already exists", taken.display());
+    }
+    let snapshot_moves: Vec<(PathBuf, PathBuf)> = moves
+        .iter()
+        .map(|(old, new)| {
+            (
+                snapshots_root(&home).join(profile_id_from_path(old, &home)),
+                snapshots_root(&home).join(profile_id_from_path(new, &home)),
+            )
+        })
+        .filter(|(old, _)| old.exists())
+        .collect();
+    if let Some((_, taken)) = snapshot_moves.iter().find(|(_, new)| new.exists()) {
+        bail!("Snapshot dir {} already exists", taken.display());
+    }
+
+    let active = current_images_path().ok();
+    let active_move = moves
+        .iter()
+        .find(|(old, _)| active.as_deref() == Some(old.to_string_lossy().as_ref()))
+        .cloned();
+
+    let mut logs = Vec::new();
+    if active_move.is_some() {
+        stop_session(&mut logs);
+    }
+
+    if let Some(parent) = new_path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed creating {}", parent.display()))?;
+    }
+    // The folder and its stores must move together, or the stores would sit
+    // under ids no profile has anymore.
+    let renames: Vec<(PathBuf, PathBuf)> =
+        std::iter::once((profile.path.clone(), new_path.clone()))
+            .chain(store_moves.iter().cloned())
+            .chain(snapshot_moves.iter().cloned())
+            .collect();
+    rename_all(&renames)?;
+    logs.push(format!(
+        "rename: moved {} -> {}",
+        profile.path.display(),
+        new_path.display()
+    ));
+    for (old, new) in &store_moves {
+        logs.push(format!(
+            "rename: moved profile store {} -> {}",
+            old.display(),
+            new.display()
+        ));
+    }
+    for (_, new) in &snapshot_moves {
+        logs.push(format!("rename: moved snapshots to {}", new.display()));
+    }
+
+    if let Some((_, new_active)) = &active_move {
+        setup_profile_userdata(new_active, &mut logs)?;
+        setup_profile_overlays(new_active, &mut logs)?;
+        set_images_path(new_active, &mut logs)?;
+        logs.push(session_left_stopped("rename"));
+    }
+
+    let mut state = SwitchState::load();
+    for (old, new) in &moves {
+        state.rename_path(&old.to_string_lossy(), &new.to_string_lossy());
+    }
+    if let Err(err) = state.save() {
+        logs.push(format!("state warning: {}", err));
+    }
+
+    Ok((new_path, logs))
+}
+
+/// Renames each pair in order. If one fails, the ones already done are moved
+/// back in reverse order before the error is returned.
+fn rename_all(renames: &[(PathBuf, PathBuf)]) -> Result<()> {
+    for (done, (old, new)) in renames.iter().enumerate() {
+        if let Err(err) = fs::rename(old, new) {
+            for (old, new) in renames[..done].iter().rev() {
+                let _ = fs::rename(new, old);
+            }
+            return Err(err)
+                .with_context(|| format!("Failed moving {} -> {}", old.display(), new.display()));
+        }
+    }
+    Ok(())
+}
+
+/// Wipes the profile's userdata and/or overlays back to a first-boot state.
+/// The overlay dirs get the `system`/`vendor` subdirs the overlay mounts
+/// expect; an active profile is left with its session stopped.
+pub fn reset_profile(profile: &ImageProfile, data: bool, overlays: bool) -> Result<Vec<String>> {
+    if !data && !overlays {
+        bail!("Nothing to reset");
+    }
+    let _lock = lock::acquire("reset")?;
+    let home = home_dir().context("Failed to resolve HOME")?;
+    let store = profiles_root(&home).join(profile_id_from_path(&profile.path, &home));
+
+    let mut logs = Vec::new();
+    let active = stop_if_running(profile, &mut logs);
+
+    let mut wiped = Vec::new();
+    if data {
+        wiped.push("data");
+    }
+    if overlays {
+        wiped.extend(["overlay_rw", "overlay_work"]);
+    }
+    for name in &wiped {
+        let dir = store.join(name);
+        let dir_s = dir.to_string_lossy().to_string();
+        run_cmd("sudo", &["rm", "-rf", &dir_s])?;
+        if *name == "data" {
+            fs::create_dir_all(&dir)
+        } else {
+            fs::create_dir_all(dir.join("system"))
+                .and_then(|()| fs::create_dir_all(dir.join("vendor")))
+        }
+        .with_context(|| format!("Failed recreating {}", dir.display()))?;
+        logs.push(format!("reset: wiped {}", dir.display()));
+    }
+
+    if active {
+        logs.push(session_left_stopped("reset"));
+    }
+    Ok(logs)
+}
+
+/// `waydroid-switch reset [--data] [--overlays] [-y] <NAME>`
+pub fn run_reset(args: &[String]) -> Result<()> {
+    let mut data = args.iter().any(|a| a == "--data");
+    let mut overlays = args.iter().any(|a| a == "--overlays");
+    if !data && !overlays {
+        data = true;
+        overlays = true;
+    }
+    let yes = args.iter().any(|a| a == "-y" || a == "--yes");
+    let Some(name) = args.iter().find(|a| !a.starts_with('-')) else {
+        bail!("reset expects a profile name");
+    };
+    let profile = find_profile(name)?;
+
+    let what = match (data, overlays) {
+        (true, true) => "userdata and overlays",
+        (true, false) => "userdata",
+        _ => "overlays",
+    };
+    let question = format!("Wipe the {} of '{}'?", what, profile.name);
+    if !yes && !crate::prompt_yes_no(&question)? {
+        bail!("Reset cancelled");
+    }
+    println!("{}", reset_profile(&profile, data, overlays)?.join("\n"));
+    Ok(())
+}
+
+/// `waydroid-switch rename <OLD> <NEW>`
+pub fn run_rename(args: &[String]) -> Result<()> {
+    let [old, new] = args else {
+        bail!("rename expects the current and the new profile name");
+    };
+    let profile = find_profile(old)?;
+    let (_, logs) = rename_profile(&profile, new)?;
+    println!("{}", logs.join("\n"));
+    Ok(())
+}
+
+/// Creates `new_name` with the same images as `source` (symlinked, or
+/// copied with `copy_images`) and a copy of its userdata and overlays.
+/// Returns the new folder path.
+pub fn clone_profile(
+    source: &ImageProfile,
+    new_name: &str,
+    copy_images: bool,
+) -> Result<(PathBuf, Vec<String>)> {
+    let _lock = lock::acquire("clone")?;
+    let home = home_dir().context("Failed to resolve HOME")?;
+    let new_path = new_profile_path(&home, new_name)?;
+    let store_root = profiles_root(&home);
+    let source_store = store_root.join(profile_id_from_path(&source.path, &home));
+    let new_store = store_root.join(profile_id_from_path(&new_path, &home));
+    if new_store.exists() {
+        bail!("Profile store {} already exists", new_store.display());
+    }
+
+    let mut logs = Vec::new();
+    let source_active =
+        current_images_path().ok().as_deref() == Some(source.path.to_string_lossy().as_ref());
+    if source_active {
+        // The running container keeps writing to the live userdata.
+        stop_session(&mut logs);
+    }
+
+    if let Err(err) = copy_profile(
+        source,
+        &source_store,
+        &new_path,
+        &new_store,
+        copy_images,
+        &mut logs,
+    ) {
+        // Leave nothing half-made behind that would block a retry.
+        let _ = fs::remove_dir_all(&new_path);
+        if new_store.exists() && fs::remove_dir_all(&new_store).is_err() {
+            let store_s = new_store.to_string_lossy().to_string();
+            let _ = run_cmd("sudo", &["rm", "-rf", &store_s]);
+        }
+        return Err(err);
+    }
+    if source_active {
+        logs.push(session_left_stopped("clone"));
+    }
+
+    Ok((new_path, logs))
+}
+
+fn copy_profile(
+    source: &ImageProfile,
+    source_store: &Path,
+    new_path: &Path,
+    new_store: &Path,
+    copy_images: bool,
+    logs: &mut Vec<String>,
+) -> Result<()> {
+    fs::create_dir_all(new_path)
+        .with_context(|| format!("Failed creating {}", new_path.display()))?;
+    for name in ["system.img", "vendor.img"] {
+        let link = source.path.join(name);
+        let image = fs::canonicalize(&link)
+            .with_context(|| format!("Failed resolving {}", link.display()))?;
+        let dst = new_path.join(name);
+        if copy_images {
+            copy_tree(&image, &dst, false)?;
+            logs.push(format!("clone: copied {}", image.display()));
+        } else {
+            symlink(&image, &dst)
+                .with_context(|| format!("Failed creating symlink {}", dst.display()))?;
+            logs.push(format!(
+                "clone: linked {} -> {}",
+                dst.display(),
+                image.display()
+            ));
+        }
+    }
+    let meta = source.path.join(PROFILE_META_FILE);
+    if meta.is_file() {
+        fs::copy(&meta, new_path.join(PROFILE_META_FILE))
+            .with_context(|| format!("Failed copying {}", meta.display()))?;
+    }
+
+    fs::create_dir_all(new_store)
+        .with_context(|| format!("Failed creating {}", new_store.display()))?;
+    for name in STORE_DIRS {
+        let src = source_store.join(name);
+        if !src.exists() {
+            logs.push(format!("clone: source has no {}, skipped", name));
+            continue;
+        }
+        copy_tree(&src, &new_store.join(name), true)?;
+        logs.push(format!("clone: copied {}", src.display()));
+    }
+    Ok(())
+}
+
+/// `waydroid-switch clone <SOURCE> <NEW> [--copy-images]`
+pub fn run_clone(args: &[String]) -> Result<()> {
+    let copy_images = args.iter().any(|a| a == "--copy-images");
+    let names: Vec<&String> = args.iter().filter(|a| !a.starts_with("--")).collect();
+    let [source, new] = names.as_slice() else {
+        bail!("clone expects the source and the new profile name");
+    };
+    let profile = find_profile(source)?;
+    let (_, logs) = clone_profile(&profile, new, copy_images)?;
+    println!("{}", logs.join("\n"));
+    Ok(())
+}
+
+pub fn find_profile(name: &str) -> Result<ImageProfile> {
+    discover_profiles()?
+        .into_iter()
+        .find(|p| p.name == name)
+        .with_context(|| format!("No profile named '{}' in ~/waydroid-images", name))
+}
+
+fn refuse_active(path: &Path, action: &str) -> Result<()> {
+    if current_images_path().ok().as_deref() == Some(path.to_string_lossy().as_ref()) {
+        bail!(
+            "Refusing to {} the active profile; switch to another one first",
+            action
+        );
+    }
+    Ok(())
+}
+
+/// Validates a name relative to `~/waydroid-images` that is not taken yet.
+pub fn new_profile_path(home: &Path, new_name: &str) -> Result<PathBuf> {
+    let new_name = new_name.trim().trim_matches('/');
+    if new_name.is_empty()
+        || Path::new(new_name).is_absolute()
+        || new_name
+            .split('/')
+            .any(|part| part.is_empty() || part == "." || part == "..")
+    {
+        bail!("Invalid profile name '{}'", new_name);
+    }
+    let new_path = home.join("waydroid-images").join(new_name);
+    if new_path.exists() {
+        bail!("{} already exists", new_path.display());
+    }
+    Ok(new_path)
+}
+
+/// `cp -a --reflink=auto` shares extents on btrfs/xfs and otherwise lets
+/// coreutils use `copy_file_range`. Store trees need root because the
+/// container owns their files.
+fn copy_tree(src: &Path, dst: &Path, as_root: bool) -> Result<()> {
+    let src_s = src.to_string_lossy().to_string();
+    let dst_s = dst.to_string_lossy().to_string();
+    let args = ["cp", "-a", "--reflink=auto", src_s.as_str(), dst_s.as_str()];
+    if as_root {
+        run_cmd("sudo", &args)?;
+    } else {
+        run_cmd(args[0], &args[1..])?;
+    }
+    Ok(())
+}
+
+fn is_images_root(dir: &Path) -> bool {
+    home_dir().map(|h| h.join("waydroid-images")).as_deref() == Some(dir)
+}
+
+fn contains_nested_profile(dir: &Path) -> bool {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return false;
+    };
+    entries.flatten().any(|entry| {
+        let path = entry.path();
+        path.is_dir()
+            && ((path.join("system.img").is_file() && path.join("vendor.img").is_file())
+                || contains_nested_profile(&path))
+    })
+}
+
+fn image_files_kb(dir: &Path) -> Option<u64> {
+    let bytes: u64 = ["system.img", "vendor.img"]
+        .iter()
+        .filter_map(|name| fs::symlink_metadata(dir.join(name)).ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum();
+    Some(bytes / 1024)
+}
+
+fn size_or_unknown(kb: Option<u64>) -> String {
+    kb.map(human_size_kb)
+        .unwrap_or_else(|| "size unknown".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rename_all_moves_back_on_failure() {
+        let dir = std::env::temp_dir().join(format!("ws-rename-all-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("a")).unwrap();
+        fs::create_dir_all(dir.join("b")).unwrap();
+        let renames = vec![
+            (dir.join("a"), dir.join("a2")),
+            (dir.join("b"), dir.join("b2")),
+            (dir.join("missing"), dir.join("c2")),
+        ];
+        assert!(rename_all(&renames).is_err());
+        assert!(dir.join("a").is_dir() && dir.join("b").is_dir());
+        assert!(!dir.join("a2").exists() && !dir.join("b2").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,226 @@
+use anyhow::{bail, Context, Result};
+use std::{
+    fs,
+    path::Path,
+    process::Command,
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+
+use crate::{
+    cfg::Ini, current_images_path, lock, run_cmd, upgrade_offline, write_waydroid_cfg, ProfileMeta,
+    PROFILE_META_FILE, WAYDROID_CFG,
+};
+
+/// Runtime props Waydroid reads from the container, stored in the active
+/// profile's userdata.
+const PERSIST_KEYS: [&str; 11] = [
+    "persist.waydroid.multi_windows",
+    "persist.waydroid.cursor_on_subsurface",
+    "persist.waydroid.invert_colors",
+    "persist.waydroid.suspend",
+    "persist.waydroid.uevent",
+    "persist.waydroid.fake_touch",
+    "persist.waydroid.fake_wifi",
+    "persist.waydroid.width",
+    "persist.waydroid.height",
+    "persist.waydroid.width_padding",
+    "persist.waydroid.height_padding",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PropSource {
+    /// `[properties]` in waydroid.cfg, baked into `waydroid_base.prop` by
+    /// `waydroid upgrade --offline`.
+    Cfg,
+    /// Set in the running session with `waydroid prop set`.
+    Persist,
+}
+
+impl PropSource {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Cfg => "cfg",
+            Self::Persist => "persist",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Prop {
+    pub key: String,
+    /// `None` for a persist prop that is unset or unreadable.
+    pub value: Option<String>,
+    pub source: PropSource,
+    /// Set by the active profile's `profile.cfg`; edits are saved there too.
+    pub profile_override: bool,
+}
+
+impl Prop {
+    pub fn summary(&self) -> String {
+        format!(
+            "[{:<7}] {} = {}{}",
+            self.source.label(),
+            self.key,
+            self.value.as_deref().unwrap_or("(unset)"),
+            if self.profile_override {
+                "  (profile.cfg)"
+            } else {
+                ""
+            }
+        )
+    }
+}
+
+/// `[properties]` of waydroid.cfg followed by the known `persist.waydroid.*`
+/// props, which can only be read while the session runs.
+pub fn load(session_running: bool) -> Result<Vec<Prop>> {
+    let ini = Ini::load(Path::new(WAYDROID_CFG))?;
+    let overridden = active_overrides();
+    let mut props: Vec<Prop> = ini
+        .section("properties")
+        .into_iter()
+        .map(|(key, value)| Prop {
+            profile_override: overridden.contains(&key),
+            key,
+            value: Some(value),
+            source: PropSource::Cfg,
+        })
+        .collect();
+    for key in PERSIST_KEYS {
+        props.push(Prop {
+            key: key.to_string(),
+            value: session_running.then(|| prop_get(key)).flatten(),
+            source: PropSource::Persist,
+            profile_override: false,
+        });
+    }
+    Ok(props)
+}
+
+/// Runs [`load`] on a worker thread: `waydroid status` and `waydroid prop
+/// get` block while a session is starting or stuck.
+#[derive(Debug)]
+pub struct PropsLoader {
+    /// Bumped by `request`; only the newest request's result is kept.
+    generation: u64,
+    requests: Sender<u64>,
+    results: Receiver<(u64, Result<Vec<Prop>>)>,
+}
+
+impl PropsLoader {
+    pub fn spawn() -> Self {
+        let (requests, jobs) = mpsc::channel::<u64>();
+        let (done, results) = mpsc::channel();
+        thread::spawn(move || {
+            for generation in jobs {
+                let props = load(crate::session_running() == Some(true));
+                if done.send((generation, props)).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            generation: 0,
+            requests,
+            results,
+        }
+    }
+
+    /// Queues a fresh load; results of earlier requests are dropped.
+    pub fn request(&mut self) {
+        self.generation += 1;
+        let _ = self.requests.send(self.generation);
+    }
+
+    /// The result of the last `request`, once it is done.
+    pub fn poll(&mut self) -> Option<Result<Vec<Prop>>> {
+        let mut latest = None;
+        while let Ok((generation, props)) = self.results.try_recv() {
+            if generation == self.generation {
+                latest = Some(props);
+            }
+        }
+        latest
+    }
+}
+
+/// Sets `key` to `value`, or removes it when `value` is `None`.
+pub fn apply(source: PropSource, key: &str, value: Option<&str>) -> Result<Vec<String>> {
+    let _lock = lock::acquire("props")?;
+    let mut logs = Vec::new();
+    match source {
+        PropSource::Cfg => {
+            let mut ini = Ini::load(Path::new(WAYDROID_CFG))?;
+            match value {
+                Some(value) => {
+                    ini.set("properties", key, value);
+                    logs.push(format!("props: [properties] {} = {}", key, value));
+                }
+                None => {
+                    if !ini.remove("properties", key) {
+                        bail!("{} is not in [properties]", key);
+                    }
+                    logs.push(format!("props: removed [properties] {}", key));
+                }
+            }
+            write_waydroid_cfg(&ini)?;
+            // Otherwise the next switch away and back would bring back the
+            // profile.cfg value.
+            if active_overrides().iter().any(|k| k == key) {
+                save_override(key, value, &mut logs)?;
+            }
+            upgrade_offline(&mut logs)?;
+            logs.push("props: takes effect on the next session start".to_string());
+        }
+        PropSource::Persist => {
+            if crate::session_running() != Some(true) {
+                bail!("persist props need a running session, start it first");
+            }
+            let value = value.unwrap_or("");
+            run_cmd("waydroid", &["prop", "set", key, value])?;
+            if value.is_empty() {
+                logs.push(format!("props: cleared {}", key));
+            } else {
+                logs.push(format!("props: {} = {}", key, value));
+            }
+        }
+    }
+    Ok(logs)
+}
+
+/// `[properties]` keys the active profile's `profile.cfg` overrides.
+fn active_overrides() -> Vec<String> {
+    let Ok(active) = current_images_path() else {
+        return Vec::new();
+    };
+    ProfileMeta::load(Path::new(&active))
+        .cfg_overrides
+        .into_iter()
+        .filter(|(section, _, _)| section == "properties")
+        .map(|(_, key, _)| key)
+        .collect()
+}
+
+fn save_override(key: &str, value: Option<&str>, logs: &mut Vec<String>) -> Result<()> {
+    let path = Path::new(&current_images_path()?).join(PROFILE_META_FILE);
+    let mut ini = Ini::load(&path)?;
+    match value {
+        Some(value) => ini.set("properties", key, value),
+        None => {
+            ini.remove("properties", key);
+        }
+    }
+    fs::write(&path, ini.render()).with_context(|| format!("Failed writing {}", path.display()))?;
+    logs.push(format!("props: saved {} in {}", key, path.display()));
+    Ok(())
+}
+
+fn prop_get(key: &str) -> Option<String> {
+    let out = Command::new("waydroid")
+        .args(["prop", "get", key])
+        .output()
+        .ok()?;
+    let value = String::from_utf8_lossy(&out.stdout).trim().to_string();
+    (out.status.success() && !value.is_empty()).then_some(value)
+}
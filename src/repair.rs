@@ -0,0 +1,264 @@
+use anyhow::{bail, Context, Result};
+use dirs::home_dir;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    current_images_path, disk_usage_kb, doctor::is_dangling_link, human_size_kb, lock,
+    profile_id_from_path, profiles_root, prompt_yes_no, run_cmd, setup_profile_overlays,
+    setup_profile_userdata, stop_session, OVERLAY_RW_LIVE, OVERLAY_WORK_LIVE,
+};
+
+#[derive(Clone, Debug)]
+enum Fix {
+    RelinkUserdata,
+    RelinkOverlays,
+    RescueOverlay { live: &'static str, rescue: PathBuf },
+    RemoveDir(PathBuf),
+    /// Nothing repair should do on its own; the summary names the command.
+    Manual,
+}
+
+#[derive(Clone, Debug)]
+pub struct Issue {
+    pub problem: String,
+    pub fix_summary: String,
+    fix: Fix,
+}
+
+/// `waydroid-switch repair [--dry-run]`. Every fix is confirmed on stdin.
+pub fn run(args: &[String]) -> Result<()> {
+    let dry_run = args.iter().any(|a| a == "--dry-run");
+    // Taken before detecting so a concurrent switch cannot change the state
+    // between the report and the fixes.
+    let _lock = if dry_run {
+        None
+    } else {
+        Some(lock::acquire("repair")?)
+    };
+    let issues = detect()?;
+    if issues.is_empty() {
+        println!("Nothing to repair.");
+        return Ok(());
+    }
+
+    let mut logs = Vec::new();
+    let mut session_stopped = false;
+    for issue in &issues {
+        println!("Problem: {}", issue.problem);
+        println!("Fix:     {}", issue.fix_summary);
+        if matches!(issue.fix, Fix::Manual) {
+            println!("Left as is.\n");
+            continue;
+        }
+        if dry_run || !prompt_yes_no("Apply this fix?")? {
+            println!("Skipped.\n");
+            continue;
+        }
+        if issue.touches_live_state() && !session_stopped {
+            stop_session(&mut logs);
+            session_stopped = true;
+        }
+        match apply(issue, &mut logs) {
+            Ok(()) => println!("Done.\n"),
+            Err(err) => println!("Failed: {}\n", err),
+        }
+    }
+
+    if !logs.is_empty() {
+        println!("{}", logs.join("\n"));
+    }
+    if session_stopped {
+        println!("Waydroid session was stopped; start it again with `waydroid session start`.");
+    }
+    Ok(())
+}
+
+impl Issue {
+    fn touches_live_state(&self) -> bool {
+        !matches!(self.fix, Fix::RemoveDir(_) | Fix::Manual)
+    }
+}
+
+pub fn detect() -> Result<Vec<Issue>> {
+    let home = home_dir().context("Failed to resolve HOME")?;
+    let waydroid_state = home.join(".local/share/waydroid");
+    let store = profiles_root(&home);
+    let active = current_images_path().ok();
+    let active_id = active
+        .as_ref()
+        .map(|p| profile_id_from_path(Path::new(p), &home));
+    let relink_target = active_id
+        .as_deref()
+        .map(|id| format!("profile '{}' from images_path", id))
+        .unwrap_or_else(|| "(images_path unknown, cannot relink)".to_string());
+
+    let mut issues = Vec::new();
+
+    let live_data = waydroid_state.join("data");
+    if is_dangling_link(&live_data) {
+        issues.push(Issue {
+            problem: format!(
+                "{} points to missing {}",
+                live_data.display(),
+                link_target(&live_data)
+            ),
+            fix_summary: format!("relink userdata to {}", relink_target),
+            fix: Fix::RelinkUserdata,
+        });
+    }
+
+    let dangling_overlays: Vec<&str> = [OVERLAY_RW_LIVE, OVERLAY_WORK_LIVE]
+        .into_iter()
+        .filter(|p| is_dangling_link(Path::new(p)))
+        .collect();
+    if !dangling_overlays.is_empty() {
+        issues.push(Issue {
+            problem: format!(
+                "{} point to deleted profile directories",
+                dangling_overlays.join(" and ")
+            ),
+            fix_summary: format!("relink overlays to {}", relink_target),
+            fix: Fix::RelinkOverlays,
+        });
+    }
+
+    // A real directory is only a problem once the switcher manages overlays;
+    // `waydroid init` recreates it and the next switch would `rm -rf` it.
+    if store_in_use(&store) {
+        for live in [OVERLAY_RW_LIVE, OVERLAY_WORK_LIVE] {
+            let path = Path::new(live);
+            if path.is_dir() && !path.is_symlink() {
+                let rescue = rescue_path(&store, live);
+                issues.push(Issue {
+                    problem: format!("{} is a real directory, not a profile link", live),
+                    fix_summary: format!(
+                        "move it to {} and relink it to {}",
+                        rescue.display(),
+                        relink_target
+                    ),
+                    fix: Fix::RescueOverlay { live, rescue },
+                });
+            }
+        }
+    }
+
+    let backup = waydroid_state.join("data.backup");
+    if backup.is_dir() {
+        issues.push(Issue {
+            problem: format!("stale userdata backup {} ({})", backup.display(), size_of(&backup)),
+            fix_summary: "delete it".to_string(),
+            fix: Fix::RemoveDir(backup),
+        });
+    }
+
+    let legacy = store.join("_legacy");
+    if legacy.is_dir() {
+        issues.push(Issue {
+            problem: format!(
+                "pre-switcher userdata in {} ({}) is not attached to any profile",
+                legacy.display(),
+                size_of(&legacy)
+            ),
+            // May be the only copy of the user's old userdata, so adopting
+            // or deleting it is left to gc.
+            fix_summary: "adopt it with `waydroid-switch gc attach _legacy <PROFILE>`, \
+                          or delete it from `gc` once it is not needed"
+                .to_string(),
+            fix: Fix::Manual,
+        });
+    }
+
+    Ok(issues)
+}
+
+fn apply(issue: &Issue, logs: &mut Vec<String>) -> Result<()> {
+    match &issue.fix {
+        Fix::RelinkUserdata => {
+            let active = active_path()?;
+            let home = home_dir().context("Failed to resolve HOME")?;
+            let live_data = home.join(".local/share/waydroid/data");
+            fs::remove_file(&live_data)
+                .with_context(|| format!("Failed removing {}", live_data.display()))?;
+            setup_profile_userdata(&active, logs)
+        }
+        Fix::RelinkOverlays => setup_profile_overlays(&active_path()?, logs),
+        Fix::RescueOverlay { live, rescue } => {
+            let active = active_path()?;
+            if let Some(parent) = rescue.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let rescue_s = rescue.to_string_lossy().to_string();
+            run_cmd("sudo", &["mv", live, &rescue_s])?;
+            logs.push(format!("repair: moved {} to {}", live, rescue.display()));
+            // Only this path: the other live overlay may be a real directory
+            // the user chose not to rescue, which setup_profile_overlays
+            // would delete.
+            relink_overlay(&active, live, logs)
+        }
+        Fix::RemoveDir(path) => {
+            let path_s = path.to_string_lossy().to_string();
+            run_cmd("sudo", &["rm", "-rf", &path_s])?;
+            logs.push(format!("repair: removed {}", path.display()));
+            Ok(())
+        }
+        Fix::Manual => Ok(()),
+    }
+}
+
+/// Points `live` at the active profile's store dir of the same name.
+fn relink_overlay(active: &Path, live: &str, logs: &mut Vec<String>) -> Result<()> {
+    let home = home_dir().context("Failed to resolve HOME")?;
+    let name = Path::new(live)
+        .file_name()
+        .context("Overlay path has no name")?;
+    let target = profiles_root(&home)
+        .join(profile_id_from_path(active, &home))
+        .join(name);
+    fs::create_dir_all(target.join("system"))?;
+    fs::create_dir_all(target.join("vendor"))?;
+    let target_s = target.to_string_lossy().to_string();
+    run_cmd("sudo", &["ln", "-s", &target_s, live])?;
+    logs.push(format!("repair: linked {} -> {}", live, target.display()));
+    Ok(())
+}
+
+fn active_path() -> Result<PathBuf> {
+    let Ok(current) = current_images_path() else {
+        bail!("images_path unknown, switch to a profile instead");
+    };
+    Ok(PathBuf::from(current))
+}
+
+fn store_in_use(store: &Path) -> bool {
+    fs::read_dir(store)
+        .map(|mut entries| entries.next().is_some())
+        .unwrap_or(false)
+}
+
+fn rescue_path(store: &Path, live: &str) -> PathBuf {
+    let name = Path::new(live)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    store.join("_recovered").join(format!("{}-{}", name, stamp))
+}
+
+fn link_target(path: &Path) -> String {
+    fs::read_link(path)
+        .map(|t| t.display().to_string())
+        .unwrap_or_else(|_| "?".to_string())
+}
+
+fn size_of(path: &Path) -> String {
+    disk_usage_kb(path)
+        .map(human_size_kb)
+        .unwrap_or_else(|| "size unknown".to_string())
+}
@@ -0,0 +1,72 @@
+use crate::ImageProfile;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatchField {
+    Name,
+    Path,
+    Meta,
+}
+
+/// Where a query matched a profile and which characters to highlight.
+#[derive(Clone, Debug)]
+pub struct ProfileMatch {
+    pub field: MatchField,
+    pub indices: Vec<usize>,
+}
+
+/// Tries name, then path, then tags/metadata, so the highlight lands on the
+/// most recognisable field.
+pub fn match_profile(query: &str, profile: &ImageProfile) -> Option<ProfileMatch> {
+    let path = profile.path.to_string_lossy();
+    let meta = profile.meta.search_text();
+    let fields: [(MatchField, &str); 3] = [
+        (MatchField::Name, &profile.name),
+        (MatchField::Path, &path),
+        (MatchField::Meta, &meta),
+    ];
+    for (field, text) in fields {
+        if let Some(indices) = fuzzy_indices(query, text) {
+            return Some(ProfileMatch { field, indices });
+        }
+    }
+    None
+}
+
+/// Case-insensitive subsequence match returning the matched char positions.
+pub fn fuzzy_indices(query: &str, text: &str) -> Option<Vec<usize>> {
+    let mut wanted = query.chars().filter(|c| !c.is_whitespace()).peekable();
+    let mut indices = Vec::new();
+    for (i, c) in text.chars().enumerate() {
+        let Some(&q) = wanted.peek() else {
+            break;
+        };
+        if c.to_lowercase().eq(q.to_lowercase()) {
+            indices.push(i);
+            wanted.next();
+        }
+    }
+    wanted.peek().is_none().then_some(indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzzy_indices_matches_subsequences_case_insensitively() {
+        assert_eq!(fuzzy_indices("lnx", "LineageX"), Some(vec![0, 2, 7]));
+        assert_eq!(fuzzy_indices("a 13", "tv/a13"), Some(vec![3, 4, 5]));
+        assert_eq!(fuzzy_indices("", "anything"), Some(vec![]));
+    }
+
+    #[test]
+    fn fuzzy_indices_counts_chars_not_bytes() {
+        assert_eq!(fuzzy_indices("é1", "café-1"), Some(vec![3, 5]));
+    }
+
+    #[test]
+    fn fuzzy_indices_rejects_out_of_order_or_missing_chars() {
+        assert_eq!(fuzzy_indices("xl", "LineageX"), None);
+        assert_eq!(fuzzy_indices("lineage21", "lineage/20"), None);
+    }
+}
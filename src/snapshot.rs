@@ -0,0 +1,396 @@
+use anyhow::{bail, Context, Result};
+use dirs::home_dir;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    current_images_path, discover_profiles, history, human_size_kb, lock, profile_id_from_path,
+    profile_ops::find_profile, profiles_root, run_cmd, session_left_stopped, session_running,
+    setup_profile_overlays, setup_profile_userdata, stop_session, ImageProfile, STORE_DIRS,
+    TAR_PRESERVE,
+};
+
+const ARCHIVE_EXT: &str = ".tar.zst";
+/// Label of the automatic snapshots, the only ones retention removes.
+pub const PRE_SWITCH_LABEL: &str = "pre-switch";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SnapshotKind {
+    /// `<created>-<label>.tar.zst` holding the store dirs.
+    Archive,
+    /// Read-only btrfs snapshot of the whole store subvolume.
+    Btrfs,
+}
+
+/// One saved state of a profile's userdata and overlays, kept under
+/// `~/.local/share/waydroid/snapshots/<profile-id>/`.
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub label: String,
+    pub created: u64,
+    pub kind: SnapshotKind,
+    pub path: PathBuf,
+}
+
+impl Snapshot {
+    fn from_path(path: PathBuf) -> Option<Self> {
+        let file_name = path.file_name()?.to_str()?.to_string();
+        let (stem, kind) = match file_name.strip_suffix(ARCHIVE_EXT) {
+            Some(stem) => (stem.to_string(), SnapshotKind::Archive),
+            None if path.is_dir() => (file_name, SnapshotKind::Btrfs),
+            None => return None,
+        };
+        let (created, label) = stem.split_once('-')?;
+        Some(Self {
+            label: label.to_string(),
+            created: created.parse().ok()?,
+            kind,
+            path,
+        })
+    }
+
+    /// What the CLI and TUI show and accept to pick a snapshot.
+    pub fn id(&self) -> String {
+        format!("{}-{}", self.created, self.label)
+    }
+
+    pub fn summary(&self) -> String {
+        let kind = match self.kind {
+            SnapshotKind::Archive => "tar.zst",
+            SnapshotKind::Btrfs => "btrfs",
+        };
+        let size = match self.kind {
+            SnapshotKind::Archive => fs::metadata(&self.path)
+                .map(|m| human_size_kb(m.len() / 1024))
+                .unwrap_or_else(|_| "size unknown".to_string()),
+            SnapshotKind::Btrfs => "shared extents".to_string(),
+        };
+        format!(
+            "{}  {}  [{}, {}]",
+            history::format_timestamp(self.created),
+            self.label,
+            kind,
+            size
+        )
+    }
+}
+
+pub fn snapshots_root(home: &Path) -> PathBuf {
+    home.join(".local/share/waydroid/snapshots")
+}
+
+/// Snapshots of `profile`, newest first.
+pub fn list(profile: &ImageProfile) -> Result<Vec<Snapshot>> {
+    let (_, dir) = profile_dirs(profile)?;
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return Ok(Vec::new());
+    };
+    let mut snapshots: Vec<Snapshot> = entries
+        .flatten()
+        .filter_map(|entry| Snapshot::from_path(entry.path()))
+        .collect();
+    snapshots.sort_by(|a, b| b.created.cmp(&a.created).then(b.label.cmp(&a.label)));
+    Ok(snapshots)
+}
+
+/// Saves the profile's store as a btrfs snapshot when the store is its own
+/// subvolume, otherwise as a tar.zst archive. The active profile's session
+/// is stopped first so the copy is consistent.
+pub fn create(profile: &ImageProfile, label: &str) -> Result<(Snapshot, Vec<String>)> {
+    let _lock = lock::acquire("snapshot")?;
+    let (store, dir) = profile_dirs(profile)?;
+    let label = sanitize_label(label);
+    let present: Vec<&str> = STORE_DIRS
+        .into_iter()
+        .filter(|name| store.join(name).exists())
+        .collect();
+    if present.is_empty() {
+        bail!(
+            "Profile '{}' has no userdata or overlays yet ({})",
+            profile.name,
+            store.display()
+        );
+    }
+
+    let mut logs = Vec::new();
+    stop_if_running(profile, &mut logs);
+    fs::create_dir_all(&dir).with_context(|| format!("Failed creating {}", dir.display()))?;
+
+    let stem = format!("{}-{}", history::unix_now(), label);
+    let store_s = store.to_string_lossy().to_string();
+    if is_btrfs_subvolume(&store) {
+        let target = dir.join(&stem);
+        let target_s = target.to_string_lossy().to_string();
+        match run_cmd(
+            "sudo",
+            &["btrfs", "subvolume", "snapshot", "-r", &store_s, &target_s],
+        ) {
+            Ok(_) => {
+                logs.push(format!("snapshot: btrfs snapshot {}", target.display()));
+                let snapshot = Snapshot::from_path(target).context("Unreadable snapshot name")?;
+                return Ok((snapshot, logs));
+            }
+            Err(err) => logs.push(format!(
+                "snapshot: btrfs snapshot failed, using an archive: {}",
+                err
+            )),
+        }
+    }
+
+    let archive = dir.join(format!("{}{}", stem, ARCHIVE_EXT));
+    let archive_s = archive.to_string_lossy().to_string();
+    let mut args = vec!["tar", "--zstd"];
+    args.extend(TAR_PRESERVE);
+    args.extend(["-cpf", &archive_s, "-C", &store_s]);
+    args.extend(present.iter().copied());
+    run_cmd("sudo", &args)?;
+    logs.push(format!(
+        "snapshot: archived {} to {}",
+        present.join(", "),
+        archive.display()
+    ));
+    let snapshot = Snapshot::from_path(archive).context("Unreadable snapshot name")?;
+    Ok((snapshot, logs))
+}
+
+/// Replaces the profile's store with `snapshot`. An active profile is left
+/// with its session stopped and its live links rechecked.
+pub fn restore(profile: &ImageProfile, snapshot: &Snapshot) -> Result<Vec<String>> {
+    let _lock = lock::acquire("snapshot")?;
+    let (store, _) = profile_dirs(profile)?;
+    let store_s = store.to_string_lossy().to_string();
+    let snapshot_s = snapshot.path.to_string_lossy().to_string();
+
+    let mut logs = Vec::new();
+    let active = stop_if_running(profile, &mut logs);
+
+    match snapshot.kind {
+        SnapshotKind::Btrfs => {
+            if store.exists() {
+                run_cmd("sudo", &["btrfs", "subvolume", "delete", &store_s])?;
+            }
+            run_cmd(
+                "sudo",
+                &["btrfs", "subvolume", "snapshot", &snapshot_s, &store_s],
+            )?;
+        }
+        SnapshotKind::Archive => {
+            for name in STORE_DIRS {
+                let dir = store.join(name).to_string_lossy().to_string();
+                run_cmd("sudo", &["rm", "-rf", &dir])?;
+            }
+            fs::create_dir_all(&store)
+                .with_context(|| format!("Failed creating {}", store.display()))?;
+            let mut args = vec!["tar", "--zstd"];
+            args.extend(TAR_PRESERVE);
+            args.extend(["-xpf", &snapshot_s, "-C", &store_s]);
+            run_cmd("sudo", &args)?;
+        }
+    }
+    logs.push(format!(
+        "snapshot: restored '{}' into {}",
+        snapshot.id(),
+        store.display()
+    ));
+
+    if active {
+        setup_profile_userdata(&profile.path, &mut logs)?;
+        setup_profile_overlays(&profile.path, &mut logs)?;
+        logs.push(session_left_stopped("snapshot"));
+    }
+    Ok(logs)
+}
+
+/// Snapshots the profile at `outgoing` before a switch relinks its userdata,
+/// then drops its oldest pre-switch snapshots beyond `keep` (0 keeps all).
+pub fn pre_switch(outgoing: &Path, keep: usize, logs: &mut Vec<String>) -> Result<()> {
+    let Some(profile) = discover_profiles()?
+        .into_iter()
+        .find(|p| p.path == outgoing)
+    else {
+        logs.push(format!(
+            "snapshot: {} is not a scanned profile, skipping pre-switch snapshot",
+            outgoing.display()
+        ));
+        return Ok(());
+    };
+    let (store, _) = profile_dirs(&profile)?;
+    if !STORE_DIRS.iter().any(|name| store.join(name).exists()) {
+        logs.push(format!(
+            "snapshot: '{}' has no userdata yet, skipping pre-switch snapshot",
+            profile.name
+        ));
+        return Ok(());
+    }
+
+    let (_, created) = create(&profile, PRE_SWITCH_LABEL)
+        .context("Pre-switch snapshot failed (set [snapshot] before_switch = false to skip)")?;
+    logs.extend(created);
+
+    if keep > 0 {
+        let expired = list(&profile)?
+            .into_iter()
+            .filter(|s| s.label == PRE_SWITCH_LABEL)
+            .skip(keep);
+        for snapshot in expired {
+            logs.extend(remove(&snapshot)?);
+        }
+    }
+    Ok(())
+}
+
+pub fn remove(snapshot: &Snapshot) -> Result<Vec<String>> {
+    let _lock = lock::acquire("snapshot")?;
+    let path_s = snapshot.path.to_string_lossy().to_string();
+    match snapshot.kind {
+        SnapshotKind::Btrfs => run_cmd("sudo", &["btrfs", "subvolume", "delete", &path_s])?,
+        SnapshotKind::Archive => run_cmd("sudo", &["rm", "-f", &path_s])?,
+    };
+    Ok(vec![format!("snapshot: removed '{}'", snapshot.id())])
+}
+
+/// `waydroid-switch snapshot <create|list|restore|delete> <PROFILE> [NAME]`
+pub fn run(args: &[String]) -> Result<()> {
+    let yes = args.iter().any(|a| a == "-y" || a == "--yes");
+    let rest: Vec<&str> = args
+        .iter()
+        .map(String::as_str)
+        .filter(|a| !a.starts_with('-'))
+        .collect();
+    let (action, profile_name, name) = match rest.as_slice() {
+        [action, profile] => (*action, *profile, None),
+        [action, profile, name] => (*action, *profile, Some(*name)),
+        _ => bail!("snapshot expects an action, a profile and for restore/delete a snapshot"),
+    };
+    let profile = find_profile(profile_name)?;
+
+    match (action, name) {
+        ("create", name) => {
+            let (_, logs) = create(&profile, name.unwrap_or("manual"))?;
+            println!("{}", logs.join("\n"));
+        }
+        ("list", None) => {
+            let snapshots = list(&profile)?;
+            if snapshots.is_empty() {
+                println!("No snapshots for '{}'", profile.name);
+            }
+            for snapshot in snapshots {
+                println!("{}  {}", snapshot.id(), snapshot.summary());
+            }
+        }
+        ("restore" | "delete", Some(name)) => {
+            let snapshot = find(&profile, name)?;
+            let question = if action == "restore" {
+                format!(
+                    "Replace the userdata and overlays of '{}' with '{}'?",
+                    profile.name,
+                    snapshot.id()
+                )
+            } else {
+                format!("Delete snapshot '{}'?", snapshot.id())
+            };
+            if !yes && !crate::prompt_yes_no(&question)? {
+                bail!("Cancelled");
+            }
+            let logs = if action == "restore" {
+                restore(&profile, &snapshot)?
+            } else {
+                remove(&snapshot)?
+            };
+            println!("{}", logs.join("\n"));
+        }
+        _ => bail!("Unknown snapshot usage; see 'waydroid-switch --help'"),
+    }
+    Ok(())
+}
+
+/// Accepts the full id or just the label (newest match wins).
+fn find(profile: &ImageProfile, name: &str) -> Result<Snapshot> {
+    list(profile)?
+        .into_iter()
+        .find(|s| s.id() == name || s.label == name)
+        .with_context(|| format!("No snapshot '{}' for '{}'", name, profile.name))
+}
+
+/// Store and snapshot directory of a profile.
+fn profile_dirs(profile: &ImageProfile) -> Result<(PathBuf, PathBuf)> {
+    let home = home_dir().context("Failed to resolve HOME")?;
+    let profile_id = profile_id_from_path(&profile.path, &home);
+    Ok((
+        profiles_root(&home).join(&profile_id),
+        snapshots_root(&home).join(&profile_id),
+    ))
+}
+
+/// Stops the session if `profile` is the active one; returns whether it is.
+pub fn stop_if_running(profile: &ImageProfile, logs: &mut Vec<String>) -> bool {
+    let active =
+        current_images_path().ok().as_deref() == Some(profile.path.to_string_lossy().as_ref());
+    if active && session_running() != Some(false) {
+        stop_session(logs);
+    }
+    active
+}
+
+fn is_btrfs_subvolume(dir: &Path) -> bool {
+    let dir_s = dir.to_string_lossy().to_string();
+    run_cmd("sudo", &["btrfs", "subvolume", "show", &dir_s]).is_ok()
+}
+
+fn sanitize_label(label: &str) -> String {
+    let cleaned: String = label
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if cleaned.is_empty() {
+        "manual".to_string()
+    } else {
+        cleaned
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_path_parses_archives() {
+        let snapshot =
+            Snapshot::from_path(PathBuf::from("/s/a_1/1792332518-clean-install.tar.zst")).unwrap();
+        assert_eq!(snapshot.created, 1_792_332_518);
+        assert_eq!(snapshot.label, "clean-install");
+        assert_eq!(snapshot.kind, SnapshotKind::Archive);
+        assert_eq!(snapshot.id(), "1792332518-clean-install");
+    }
+
+    #[test]
+    fn from_path_takes_dirs_as_btrfs_snapshots() {
+        let dir = std::env::temp_dir().join(format!("waydroid-switch-test-{}", std::process::id()));
+        let snap = dir.join("1792332518-pre-switch");
+        fs::create_dir_all(&snap).unwrap();
+        let parsed = Snapshot::from_path(snap.clone());
+        let missing = Snapshot::from_path(dir.join("1792332519-gone"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        let parsed = parsed.unwrap();
+        assert_eq!(parsed.kind, SnapshotKind::Btrfs);
+        assert_eq!(parsed.label, "pre-switch");
+        assert!(missing.is_none());
+    }
+
+    #[test]
+    fn from_path_rejects_foreign_names() {
+        assert!(Snapshot::from_path(PathBuf::from("/s/notes.txt")).is_none());
+        assert!(Snapshot::from_path(PathBuf::from("/s/nolabel.tar.zst")).is_none());
+        assert!(Snapshot::from_path(PathBuf::from("/s/abc-label.tar.zst")).is_none());
+    }
+}
@@ -0,0 +1,110 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf};
+
+use crate::app_state_dir;
+
+/// Which profiles were last switched to, kept independently of
+/// `images_path` so a manual cfg edit does not lose the round trip.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SwitchState {
+    pub current: Option<String>,
+    pub previous: Option<String>,
+    /// waydroid.cfg values the current profile's overrides replaced.
+    #[serde(default)]
+    pub cfg_restore: Vec<SavedCfgValue>,
+}
+
+/// A waydroid.cfg entry as it was before an override, `None` if absent.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavedCfgValue {
+    pub section: String,
+    pub key: String,
+    pub value: Option<String>,
+}
+
+impl SwitchState {
+    pub fn load() -> Self {
+        state_path()
+            .ok()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .and_then(|raw| serde_json::from_str(&raw).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = state_path()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed creating {}", parent.display()))?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed writing {}", tmp.display()))?;
+        fs::rename(&tmp, &path).with_context(|| format!("Failed writing {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Called after a successful switch. `from` is only a fallback for the
+    /// first switch, before anything has been recorded.
+    pub fn record_switch(&mut self, to: &str, from: Option<&str>) {
+        let outgoing = self.current.clone().or_else(|| from.map(str::to_string));
+        if outgoing.as_deref() != Some(to) {
+            self.previous = outgoing;
+        }
+        self.current = Some(to.to_string());
+    }
+
+    /// Follows a profile folder that was renamed or moved.
+    pub fn rename_path(&mut self, from: &str, to: &str) {
+        for slot in [&mut self.current, &mut self.previous] {
+            if slot.as_deref() == Some(from) {
+                *slot = Some(to.to_string());
+            }
+        }
+    }
+}
+
+fn state_path() -> Result<PathBuf> {
+    Ok(app_state_dir()?.join("state.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_switch_keeps_the_outgoing_profile_as_previous() {
+        let mut state = SwitchState::default();
+        state.record_switch("/images/a", Some("/images/x"));
+        assert_eq!(state.current.as_deref(), Some("/images/a"));
+        assert_eq!(state.previous.as_deref(), Some("/images/x"));
+
+        // Once recorded, `from` no longer matters.
+        state.record_switch("/images/b", Some("/images/y"));
+        assert_eq!(state.current.as_deref(), Some("/images/b"));
+        assert_eq!(state.previous.as_deref(), Some("/images/a"));
+    }
+
+    #[test]
+    fn record_switch_to_the_same_profile_keeps_previous() {
+        let mut state = SwitchState::default();
+        state.record_switch("/images/a", None);
+        assert_eq!(state.previous, None);
+        state.record_switch("/images/b", None);
+        state.record_switch("/images/b", None);
+        assert_eq!(state.previous.as_deref(), Some("/images/a"));
+    }
+
+    #[test]
+    fn rename_path_follows_current_and_previous() {
+        let mut state = SwitchState {
+            current: Some("/images/a".to_string()),
+            previous: Some("/images/a".to_string()),
+            cfg_restore: Vec::new(),
+        };
+        state.rename_path("/images/a", "/images/z");
+        assert_eq!(state.current.as_deref(), Some("/images/z"));
+        assert_eq!(state.previous.as_deref(), Some("/images/z"));
+    }
+}
@@ -0,0 +1,145 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::ImageProfile;
+
+/// One visible line of the folder tree. A folder that holds images itself
+/// is both a folder row and a profile row.
+#[derive(Clone, Debug)]
+pub struct TreeRow {
+    pub depth: usize,
+    pub label: String,
+    pub folder: Option<String>,
+    pub profile: Option<usize>,
+    pub count: usize,
+    pub expanded: bool,
+}
+
+#[derive(Default)]
+struct Node {
+    children: BTreeMap<String, Node>,
+    profile: Option<usize>,
+}
+
+impl Node {
+    fn count(&self) -> usize {
+        usize::from(self.profile.is_some()) + self.children.values().map(Node::count).sum::<usize>()
+    }
+}
+
+/// Flattens the profiles at `visible` (indices into `profiles`) into rows,
+/// skipping the contents of folders listed in `collapsed`.
+pub fn build_rows(
+    profiles: &[ImageProfile],
+    visible: &[usize],
+    collapsed: &BTreeSet<String>,
+) -> Vec<TreeRow> {
+    let mut root = Node::default();
+    for &idx in visible {
+        let mut node = &mut root;
+        for part in profiles[idx].name.split('/') {
+            node = node.children.entry(part.to_string()).or_default();
+        }
+        node.profile = Some(idx);
+    }
+
+    let mut rows = Vec::new();
+    flatten(&root, "", 0, collapsed, &mut rows);
+    rows
+}
+
+fn flatten(
+    node: &Node,
+    prefix: &str,
+    depth: usize,
+    collapsed: &BTreeSet<String>,
+    rows: &mut Vec<TreeRow>,
+) {
+    for (name, child) in &node.children {
+        let key = if prefix.is_empty() {
+            name.clone()
+        } else {
+            format!("{}/{}", prefix, name)
+        };
+        let is_folder = !child.children.is_empty();
+        let expanded = is_folder && !collapsed.contains(&key);
+        rows.push(TreeRow {
+            depth,
+            label: name.clone(),
+            folder: is_folder.then(|| key.clone()),
+            profile: child.profile,
+            count: child.count(),
+            expanded,
+        });
+        if expanded {
+            flatten(child, &key, depth + 1, collapsed, rows);
+        }
+    }
+}
+
+/// Row of the folder containing `rows[cursor]`, for collapsing from a leaf.
+pub fn parent_row(rows: &[TreeRow], cursor: usize) -> Option<usize> {
+    let depth = rows.get(cursor)?.depth;
+    (0..cursor).rev().find(|&i| rows[i].depth < depth)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn profiles(names: &[&str]) -> Vec<ImageProfile> {
+        names
+            .iter()
+            .map(|name| ImageProfile {
+                name: name.to_string(),
+                path: PathBuf::from("/images").join(name),
+                meta: Default::default(),
+            })
+            .collect()
+    }
+
+    fn labels(rows: &[TreeRow]) -> Vec<(usize, &str, usize)> {
+        rows.iter()
+            .map(|r| (r.depth, r.label.as_str(), r.count))
+            .collect()
+    }
+
+    #[test]
+    fn build_rows_nests_folders_sorted_with_counts() {
+        let profiles = profiles(&["tv/a13", "lineage", "tv/a11", "tv/old/a9"]);
+        let rows = build_rows(&profiles, &[0, 1, 2, 3], &BTreeSet::new());
+        assert_eq!(
+            labels(&rows),
+            vec![
+                (0, "lineage", 1),
+                (0, "tv", 3),
+                (1, "a11", 1),
+                (1, "a13", 1),
+                (1, "old", 1),
+                (2, "a9", 1),
+            ]
+        );
+        assert_eq!(rows[1].folder.as_deref(), Some("tv"));
+        assert_eq!(rows[4].folder.as_deref(), Some("tv/old"));
+        assert_eq!(rows[3].profile, Some(0));
+        assert_eq!(rows[1].profile, None);
+    }
+
+    #[test]
+    fn build_rows_skips_collapsed_and_invisible_profiles() {
+        let profiles = profiles(&["tv/a13", "lineage", "tv/a11"]);
+        let collapsed = BTreeSet::from(["tv".to_string()]);
+        let rows = build_rows(&profiles, &[0, 2], &collapsed);
+        assert_eq!(labels(&rows), vec![(0, "tv", 2)]);
+        assert!(!rows[0].expanded);
+    }
+
+    #[test]
+    fn parent_row_finds_the_enclosing_folder() {
+        let profiles = profiles(&["tv/a13", "tv/old/a9"]);
+        let rows = build_rows(&profiles, &[0, 1], &BTreeSet::new());
+        assert_eq!(parent_row(&rows, 3), Some(2));
+        assert_eq!(parent_row(&rows, 1), Some(0));
+        assert_eq!(parent_row(&rows, 0), None);
+    }
+}
@@ -0,0 +1,175 @@
+use anyhow::{bail, Context, Result};
+use dirs::home_dir;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    current_images_path, history, lock, overrides, profile_id_from_path, profile_ops::find_profile,
+    profiles_root, run_cmd, set_images_path, state::SwitchState, stop_session, upgrade_offline,
+    OVERLAY_RW_LIVE, OVERLAY_WORK_LIVE, TAR_PRESERVE,
+};
+
+/// `waydroid-switch uninstall [--profile NAME] [--copy] [--archive] [-y]`
+///
+/// Puts real `data`/`overlay_rw`/`overlay_work` directories back where
+/// Waydroid expects them, filled from the chosen profile's store, sets its
+/// `images_path` and undoes the profile.cfg overrides in waydroid.cfg.
+/// `--copy` leaves the store intact, `--archive` packs the remaining stores
+/// into one tar.zst and removes them.
+pub fn run(args: &[String]) -> Result<()> {
+    let copy = args.iter().any(|a| a == "--copy");
+    let archive = args.iter().any(|a| a == "--archive");
+    let yes = args.iter().any(|a| a == "-y" || a == "--yes");
+    let profile_name = args
+        .iter()
+        .position(|a| a == "--profile")
+        .map(|i| args.get(i + 1).context("--profile expects a name"))
+        .transpose()?;
+
+    let home = home_dir().context("Failed to resolve HOME")?;
+    let images = match profile_name {
+        Some(name) => find_profile(name)?.path,
+        None => PathBuf::from(
+            current_images_path()
+                .context("images_path unknown, pick the profile to keep with --profile")?,
+        ),
+    };
+    let store = profiles_root(&home).join(profile_id_from_path(&images, &home));
+    let live_data = home.join(".local/share/waydroid/data");
+    let targets = [
+        ("data", live_data),
+        ("overlay_rw", PathBuf::from(OVERLAY_RW_LIVE)),
+        ("overlay_work", PathBuf::from(OVERLAY_WORK_LIVE)),
+    ];
+
+    println!("Keep:        {}", images.display());
+    println!("From store:  {}", store.display());
+    for (name, live) in &targets {
+        println!(
+            "  {:<12} -> {} ({})",
+            name,
+            live.display(),
+            if copy { "copied" } else { "moved" }
+        );
+    }
+    let overridden = SwitchState::load().cfg_restore.len();
+    if overridden > 0 {
+        println!("Overrides:   {} waydroid.cfg values put back", overridden);
+    }
+    println!(
+        "Other stores: {}",
+        if archive {
+            "archived to ~/waydroid-switch-stores-<time>.tar.zst, then removed"
+        } else {
+            "left in place"
+        }
+    );
+    if !yes && !crate::prompt_yes_no("Restore the stock Waydroid layout?")? {
+        bail!("Uninstall cancelled");
+    }
+
+    let _lock = lock::acquire("uninstall")?;
+    let mut logs = Vec::new();
+    stop_session(&mut logs);
+
+    for (name, live) in &targets {
+        restore_dir(&store.join(name), live, name, copy, &mut logs)?;
+    }
+    set_images_path(&images, &mut logs)?;
+    if overrides::clear(&mut logs)? {
+        upgrade_offline(&mut logs)?;
+    }
+
+    if archive {
+        archive_stores(&home, &mut logs)?;
+    }
+
+    logs.push(
+        "uninstall: done; start Waydroid as usual, ~/waydroid-images and the switcher state are untouched"
+            .to_string(),
+    );
+    println!("{}", logs.join("\n"));
+    Ok(())
+}
+
+/// Replaces the symlink at `live` with a real directory holding `source`.
+fn restore_dir(
+    source: &Path,
+    live: &Path,
+    name: &str,
+    copy: bool,
+    logs: &mut Vec<String>,
+) -> Result<()> {
+    if live.exists() && !live.is_symlink() {
+        logs.push(format!(
+            "uninstall: {} is already a real directory, kept",
+            live.display()
+        ));
+        return Ok(());
+    }
+
+    let live_s = live.to_string_lossy().to_string();
+    let source_s = source.to_string_lossy().to_string();
+    if live.is_symlink() {
+        run_cmd("sudo", &["rm", "-f", &live_s])?;
+    }
+    if !source.exists() {
+        // Userdata lives in the user's home and must stay theirs; only the
+        // overlay dirs under /var/lib/waydroid need root.
+        if name == "data" {
+            fs::create_dir_all(live)
+                .with_context(|| format!("Failed creating {}", live.display()))?;
+        } else {
+            run_cmd("sudo", &["mkdir", "-p", &live_s])?;
+        }
+        logs.push(format!(
+            "uninstall: profile has no {}, created empty {}",
+            name,
+            live.display()
+        ));
+    } else if copy {
+        run_cmd("sudo", &["cp", "-a", "--reflink=auto", &source_s, &live_s])?;
+        logs.push(format!(
+            "uninstall: copied {} -> {}",
+            source.display(),
+            live.display()
+        ));
+    } else {
+        run_cmd("sudo", &["mv", &source_s, &live_s])?;
+        logs.push(format!(
+            "uninstall: moved {} -> {}",
+            source.display(),
+            live.display()
+        ));
+    }
+    Ok(())
+}
+
+fn archive_stores(home: &Path, logs: &mut Vec<String>) -> Result<()> {
+    let root = profiles_root(home);
+    if !root.exists() {
+        return Ok(());
+    }
+    let archive = home.join(format!(
+        "waydroid-switch-stores-{}.tar.zst",
+        history::unix_now()
+    ));
+    let archive_s = archive.to_string_lossy().to_string();
+    let root_s = root.to_string_lossy().to_string();
+    let mut args = vec!["tar", "--zstd"];
+    args.extend(TAR_PRESERVE);
+    args.extend(["-cpf", &archive_s, "-C", &root_s, "."]);
+    run_cmd("sudo", &args)?;
+    // Root-owned after sudo tar; hand it back to the invoking user.
+    if let Ok(user) = env::var("SUDO_USER").or_else(|_| env::var("USER")) {
+        let _ = run_cmd("sudo", &["chown", &user, &archive_s]);
+    }
+    run_cmd("sudo", &["rm", "-rf", &root_s])?;
+    logs.push(format!(
+        "uninstall: archived the remaining profile stores to {}",
+        archive.display()
+    ));
+    Ok(())
+}
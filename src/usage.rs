@@ -0,0 +1,170 @@
+use anyhow::{Context, Result};
+use dirs::home_dir;
+use serde::Serialize;
+use std::{collections::HashMap, fs, os::unix::fs::MetadataExt};
+
+use crate::{
+    discover_profiles, disk_usage_kb, human_size_kb, profile_id_from_path, profiles_root,
+    snapshot::snapshots_root, ImageProfile,
+};
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ProfileUsage {
+    pub name: String,
+    pub images_kb: u64,
+    /// Part of `images_kb` also used by other profiles (symlinks or
+    /// hardlinks to the same file).
+    pub shared_kb: u64,
+    pub data_kb: u64,
+    pub overlays_kb: u64,
+}
+
+impl ProfileUsage {
+    /// What deleting the profile with its userdata would free.
+    pub fn exclusive_kb(&self) -> u64 {
+        self.images_kb - self.shared_kb + self.data_kb + self.overlays_kb
+    }
+}
+
+/// Per-profile breakdown, largest exclusive usage first, plus totals that
+/// count every image file once.
+#[derive(Clone, Debug, Serialize)]
+pub struct UsageReport {
+    pub profiles: Vec<ProfileUsage>,
+    pub unique_images_kb: u64,
+    pub data_kb: u64,
+    pub overlays_kb: u64,
+    /// All of `~/.local/share/waydroid/profiles`, orphaned stores included.
+    pub store_kb: Option<u64>,
+    pub snapshots_kb: Option<u64>,
+}
+
+impl UsageReport {
+    pub fn collect(profiles: &[ImageProfile]) -> Result<Self> {
+        let home = home_dir().context("Failed to resolve HOME")?;
+        let store_root = profiles_root(&home);
+
+        // (dev, inode) -> (size, number of profiles using it)
+        let mut files: HashMap<(u64, u64), (u64, usize)> = HashMap::new();
+        let mut per_profile = Vec::new();
+        for profile in profiles {
+            let mut keys = Vec::new();
+            for name in ["system.img", "vendor.img"] {
+                let Ok(meta) = fs::metadata(profile.path.join(name)) else {
+                    continue;
+                };
+                let key = (meta.dev(), meta.ino());
+                let entry = files.entry(key).or_insert((meta.blocks() / 2, 0));
+                entry.1 += 1;
+                keys.push(key);
+            }
+            let store = store_root.join(profile_id_from_path(&profile.path, &home));
+            let kb = |name: &str| disk_usage_kb(&store.join(name)).unwrap_or(0);
+            per_profile.push((
+                profile.name.clone(),
+                keys,
+                kb("data"),
+                kb("overlay_rw") + kb("overlay_work"),
+            ));
+        }
+
+        let mut rows: Vec<ProfileUsage> = per_profile
+            .into_iter()
+            .map(|(name, keys, data_kb, overlays_kb)| {
+                let images_kb = keys.iter().map(|k| files[k].0).sum();
+                let shared_kb = keys
+                    .iter()
+                    .filter(|k| files[k].1 > 1)
+                    .map(|k| files[k].0)
+                    .sum();
+                ProfileUsage {
+                    name,
+                    images_kb,
+                    shared_kb,
+                    data_kb,
+                    overlays_kb,
+                }
+            })
+            .collect();
+        rows.sort_by(|a, b| {
+            b.exclusive_kb()
+                .cmp(&a.exclusive_kb())
+                .then_with(|| a.name.cmp(&b.name))
+        });
+
+        let snapshots = snapshots_root(&home);
+        Ok(Self {
+            unique_images_kb: files.values().map(|(kb, _)| kb).sum(),
+            data_kb: rows.iter().map(|r| r.data_kb).sum(),
+            overlays_kb: rows.iter().map(|r| r.overlays_kb).sum(),
+            store_kb: disk_usage_kb(&store_root),
+            snapshots_kb: snapshots
+                .exists()
+                .then(|| disk_usage_kb(&snapshots))
+                .flatten(),
+            profiles: rows,
+        })
+    }
+
+    pub fn header() -> String {
+        format!(
+            "{:<32} {:>10} {:>10} {:>10} {:>10} {:>10}",
+            "PROFILE", "IMAGES", "SHARED", "DATA", "OVERLAYS", "EXCLUSIVE"
+        )
+    }
+
+    pub fn row(usage: &ProfileUsage) -> String {
+        format!(
+            "{:<32} {:>10} {:>10} {:>10} {:>10} {:>10}",
+            usage.name,
+            human_size_kb(usage.images_kb),
+            human_size_kb(usage.shared_kb),
+            human_size_kb(usage.data_kb),
+            human_size_kb(usage.overlays_kb),
+            human_size_kb(usage.exclusive_kb())
+        )
+    }
+
+    pub fn totals_lines(&self) -> Vec<String> {
+        let optional = |kb: Option<u64>| kb.map(human_size_kb).unwrap_or_else(|| "-".to_string());
+        vec![
+            format!(
+                "Images (each file once): {}",
+                human_size_kb(self.unique_images_kb)
+            ),
+            format!("Userdata: {}", human_size_kb(self.data_kb)),
+            format!("Overlays: {}", human_size_kb(self.overlays_kb)),
+            format!(
+                "Profile store (incl. orphaned): {}",
+                optional(self.store_kb)
+            ),
+            format!("Snapshots: {}", optional(self.snapshots_kb)),
+            format!(
+                "Total: {}",
+                human_size_kb(
+                    self.unique_images_kb
+                        + self.store_kb.unwrap_or(self.data_kb + self.overlays_kb)
+                        + self.snapshots_kb.unwrap_or(0)
+                )
+            ),
+        ]
+    }
+}
+
+/// `waydroid-switch du [--json]`
+pub fn run(args: &[String]) -> Result<()> {
+    let report = UsageReport::collect(&discover_profiles()?)?;
+    if args.iter().any(|a| a == "--json") {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    println!("{}", UsageReport::header());
+    for usage in &report.profiles {
+        println!("{}", UsageReport::row(usage));
+    }
+    println!();
+    for line in report.totals_lines() {
+        println!("{}", line);
+    }
+    Ok(())
+}